@@ -18,6 +18,14 @@ pub fn parser(c: &mut Criterion) {
         })
     });
 
+    c.bench_function("libyaml-safer parse large (slice)", |b| {
+        b.iter(|| {
+            let mut parser = Parser::new();
+            parser.set_input_slice(VERY_LARGE_YAML);
+            Document::load(&mut parser)
+        })
+    });
+
     c.bench_function("unsafe-libyaml parse large", |b| {
         b.iter(|| unsafe {
             let mut parser = MaybeUninit::zeroed();
@@ -53,7 +61,6 @@ pub fn parser(c: &mut Criterion) {
         b.iter_custom(|iters| {
             let mut measurement = std::time::Duration::ZERO;
             for _ in 0..iters {
-                let doc = doc.clone();
                 let start_time = std::time::Instant::now();
                 let mut emitter = Emitter::new();
                 emitter.set_output(&mut buffer);
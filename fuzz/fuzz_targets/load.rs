@@ -1,7 +1,7 @@
 #![no_main]
 
 use libfuzzer_sys::fuzz_target;
-use libyaml_safer::{Document, Parser};
+use libyaml_safer::Parser;
 
 fuzz_target!(|data: &[u8]| fuzz_target(data));
 
@@ -9,10 +9,5 @@ fn fuzz_target(mut data: &[u8]) {
     let mut parser = Parser::new();
     parser.set_input(&mut data);
 
-    while let Ok(mut document) = Document::load(&mut parser) {
-        let done = document.get_root_node().is_none();
-        if done {
-            break;
-        }
-    }
+    for _document in parser.documents() {}
 }
@@ -54,7 +54,7 @@ pub(crate) fn test_main(
                 }
                 _ = writeln!(stdout);
             }
-            EventData::DocumentEnd { implicit } => {
+            EventData::DocumentEnd { implicit, .. } => {
                 _ = write!(stdout, "-DOC");
                 if !implicit {
                     _ = write!(stdout, " ...");
@@ -99,7 +99,7 @@ pub(crate) fn test_main(
                 }
                 _ = writeln!(stdout);
             }
-            EventData::SequenceEnd => {
+            EventData::SequenceEnd { .. } => {
                 _ = writeln!(stdout, "-SEQ");
             }
             EventData::MappingStart { anchor, tag, .. } => {
@@ -112,7 +112,7 @@ pub(crate) fn test_main(
                 }
                 _ = writeln!(stdout);
             }
-            EventData::MappingEnd => {
+            EventData::MappingEnd { .. } => {
                 _ = writeln!(stdout, "-MAP");
             }
         }
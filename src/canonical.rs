@@ -0,0 +1,412 @@
+use core::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    Document, Error, MappingStyle, Mark, Node, NodeData, NodeId, NodePair, ParserLimits, Result,
+    ScalarStyle, SequenceStyle, DEFAULT_MAPPING_TAG, DEFAULT_SCALAR_TAG, DEFAULT_SEQUENCE_TAG,
+};
+
+impl Document {
+    /// Produce the canonical form of the document.
+    ///
+    /// In the canonical form, every node has a resolved tag, aliases are
+    /// expanded into copies of the nodes they refer to, and the pairs of a
+    /// mapping are sorted by key. Keys are ordered by kind (scalars, then
+    /// sequences, then mappings), then by tag, then by value, item by item for
+    /// a collection. Styles, anchors and directives are dropped; marks are
+    /// kept.
+    ///
+    /// Documents with the same content have the same canonical form. A
+    /// recursive alias cannot be expanded, and is an error.
+    ///
+    /// Expanding aliases can make the canonical form exponentially larger
+    /// than the document. Like
+    /// [`ParserLimits::max_alias_expansion`](crate::ParserLimits::max_alias_expansion)
+    /// for [`Document::load()`], `max_nodes` limits the number of nodes of the
+    /// canonical form, and exceeding it is an error.
+    pub fn canonicalize(&self, max_nodes: Option<usize>) -> Result<Document> {
+        let mut canonical = Document::new(None, &[], true, true);
+        if !self.nodes.is_empty() {
+            let root = NodeId::from_index(0);
+            self.canonical_node(root, &mut canonical, max_nodes)?;
+        }
+        Ok(canonical)
+    }
+
+    /// A stable hash of the content of the document.
+    ///
+    /// See [`Node::fingerprint()`].
+    pub fn fingerprint(&self) -> u64 {
+        match self.nodes.first() {
+            Some(root) => root.fingerprint(self),
+            None => Fingerprint::new().finish(),
+        }
+    }
+
+    /// Copy the node `index` and its children into `canonical`.
+    ///
+    /// Collections are copied with an explicit stack, so that deeply nested
+    /// nodes cannot overflow the call stack.
+    fn canonical_node(
+        &self,
+        index: NodeId,
+        canonical: &mut Document,
+        max_nodes: Option<usize>,
+    ) -> Result<NodeId> {
+        // The collections being copied, to detect recursive aliases.
+        let mut ancestors = HashSet::new();
+        let mut frames: Vec<CanonicalFrame> = Vec::new();
+        let mut result =
+            self.start_canonical_node(index, canonical, &mut ancestors, &mut frames, max_nodes)?;
+        while let Some(frame) = frames.last_mut() {
+            frame.children.extend(result);
+            let node = &self.nodes[frame.index.index()];
+            if let Some(child) = node.child(frame.children.len()) {
+                result = self.start_canonical_node(
+                    child,
+                    canonical,
+                    &mut ancestors,
+                    &mut frames,
+                    max_nodes,
+                )?;
+                continue;
+            }
+            let Some(frame) = frames.pop() else {
+                unreachable!()
+            };
+            ancestors.remove(&frame.index);
+            let data = if let NodeData::Sequence { .. } = node.data {
+                NodeData::Sequence {
+                    items: frame.children,
+                    style: SequenceStyle::Any,
+                }
+            } else {
+                let mut pairs: Vec<NodePair> = frame
+                    .children
+                    .chunks(2)
+                    .map(|pair| NodePair {
+                        key: pair[0],
+                        value: pair[1],
+                    })
+                    .collect();
+                pairs.sort_by(|a, b| {
+                    canonical
+                        .canonical_cmp(a.key, b.key)
+                        .then_with(|| canonical.canonical_cmp(a.value, b.value))
+                });
+                NodeData::Mapping {
+                    pairs,
+                    style: MappingStyle::Any,
+                }
+            };
+            canonical.nodes[frame.id.index()] = node.canonical_copy(data);
+            result = Some(frame.id);
+        }
+        let Some(id) = result else { unreachable!() };
+        Ok(id)
+    }
+
+    /// Start copying the node `index` into `canonical`.
+    ///
+    /// Returns the id of the copy of a scalar, or else pushes the collection
+    /// onto `frames` and returns `None`.
+    fn start_canonical_node(
+        &self,
+        index: NodeId,
+        canonical: &mut Document,
+        ancestors: &mut HashSet<NodeId>,
+        frames: &mut Vec<CanonicalFrame>,
+        max_nodes: Option<usize>,
+    ) -> Result<Option<NodeId>> {
+        let node = &self.nodes[index.index()];
+        if ancestors.contains(&index) {
+            return Err(Error::composer(
+                "",
+                Mark::default(),
+                "found recursive alias",
+                node.start_mark,
+            ));
+        }
+        if ParserLimits::exceeds(max_nodes, canonical.nodes.len() + 1) {
+            return Err(Error::composer(
+                "",
+                Mark::default(),
+                "exceeded the alias expansion limit",
+                node.start_mark,
+            ));
+        }
+        // Reserve the id of the node before its children, so that the root
+        // node stays the first node.
+        canonical.nodes.push(Node::default());
+        let id = NodeId::from_index(canonical.nodes.len() - 1);
+        let data = match &node.data {
+            NodeData::NoNode => NodeData::NoNode,
+            NodeData::Scalar { value, .. } => NodeData::Scalar {
+                value: value.clone(),
+                style: ScalarStyle::Any,
+            },
+            NodeData::Sequence { .. } | NodeData::Mapping { .. } => {
+                ancestors.insert(index);
+                frames.push(CanonicalFrame {
+                    index,
+                    id,
+                    children: Vec::new(),
+                });
+                return Ok(None);
+            }
+        };
+        canonical.nodes[id.index()] = node.canonical_copy(data);
+        Ok(Some(id))
+    }
+
+    /// The canonical key order. Only called on canonical documents, which have
+    /// no recursive aliases.
+    ///
+    /// Nodes are compared in document order with an explicit stack, so that
+    /// deeply nested nodes cannot overflow the call stack.
+    fn canonical_cmp(&self, a: NodeId, b: NodeId) -> Ordering {
+        // The pairs of collections being compared, and the position of their
+        // next children.
+        let mut stack = Vec::new();
+        let mut next = Some((a, b));
+        loop {
+            if let Some((a, b)) = next {
+                let (node_a, node_b) = (&self.nodes[a.index()], &self.nodes[b.index()]);
+                let ordering = node_a
+                    .kind_rank()
+                    .cmp(&node_b.kind_rank())
+                    .then_with(|| node_a.resolved_tag().cmp(node_b.resolved_tag()))
+                    .then_with(|| match (&node_a.data, &node_b.data) {
+                        (
+                            NodeData::Scalar { value: value_a, .. },
+                            NodeData::Scalar { value: value_b, .. },
+                        ) => value_a.cmp(value_b),
+                        _ => Ordering::Equal,
+                    });
+                if ordering.is_ne() {
+                    return ordering;
+                }
+                stack.push((node_a, node_b, 0));
+            }
+            let Some((node_a, node_b, position)) = stack.last_mut() else {
+                return Ordering::Equal;
+            };
+            // A collection that runs out of children first is the lesser.
+            next = match (node_a.child(*position), node_b.child(*position)) {
+                (Some(a), Some(b)) => {
+                    *position += 1;
+                    Some((a, b))
+                }
+                (Some(_), None) => return Ordering::Greater,
+                (None, Some(_)) => return Ordering::Less,
+                (None, None) => {
+                    stack.pop();
+                    None
+                }
+            };
+        }
+    }
+}
+
+impl Node {
+    /// A stable hash of the content of the node and its children in
+    /// `document`.
+    ///
+    /// The hash is that of the canonical form (see
+    /// [`Document::canonicalize()`]): it depends on the resolved tags and the
+    /// values of the nodes, and not on styles, marks, anchors, or the order of
+    /// mapping pairs. It is the same across runs, platforms and versions of
+    /// Rust, but is not a cryptographic hash. A recursive alias is hashed as a
+    /// reference to the collection it refers to.
+    pub fn fingerprint(&self, document: &Document) -> u64 {
+        // The hashes of the nodes that do not refer to the collections being
+        // hashed.
+        let mut known = HashMap::new();
+        // The collections being hashed, with their positions, to detect
+        // recursive aliases.
+        let mut ancestors = HashMap::new();
+        let mut frames = vec![FingerprintFrame::new(self, None, 0)];
+        loop {
+            let Some(frame) = frames.last_mut() else {
+                unreachable!()
+            };
+            if let Some(id) = frame.node.child(frame.next) {
+                frame.next += 1;
+                if let Some(position) = ancestors.get(&id) {
+                    frame.lowest = frame.lowest.min(*position);
+                    let mut reference = Fingerprint::new();
+                    reference.write_u64((ancestors.len() - position) as u64);
+                    frame.add(reference.finish());
+                } else if let Some(hash) = known.get(&id) {
+                    frame.add(*hash);
+                } else {
+                    let position = ancestors.len();
+                    ancestors.insert(id, position);
+                    frames.push(FingerprintFrame::new(
+                        &document.nodes[id.index()],
+                        Some(id),
+                        position,
+                    ));
+                }
+                continue;
+            }
+            let Some(mut frame) = frames.pop() else {
+                unreachable!()
+            };
+            let hash = frame.finish();
+            let Some(id) = frame.id else {
+                return hash;
+            };
+            ancestors.remove(&id);
+            let Some(parent) = frames.last_mut() else {
+                unreachable!()
+            };
+            // A reference to an ancestor is relative, so the hash of a node
+            // that only refers to itself and its children holds anywhere.
+            if frame.lowest >= frame.position {
+                known.insert(id, hash);
+            } else {
+                parent.lowest = parent.lowest.min(frame.lowest);
+            }
+            parent.add(hash);
+        }
+    }
+
+    /// A copy of the node with the data `data` for a canonical document.
+    fn canonical_copy(&self, data: NodeData) -> Node {
+        Node {
+            data,
+            tag: Some(String::from(self.resolved_tag())),
+            schema: self.schema,
+            anchor: None,
+            start_mark: self.start_mark,
+            end_mark: self.end_mark,
+        }
+    }
+
+    /// The tag of the node, or the default tag of its kind if it has none.
+    fn resolved_tag(&self) -> &str {
+        match (self.tag.as_deref(), &self.data) {
+            (Some(tag), _) if tag != "!" => tag,
+            (_, NodeData::Sequence { .. }) => DEFAULT_SEQUENCE_TAG,
+            (_, NodeData::Mapping { .. }) => DEFAULT_MAPPING_TAG,
+            _ => DEFAULT_SCALAR_TAG,
+        }
+    }
+
+    fn kind_rank(&self) -> u64 {
+        match self.data {
+            NodeData::NoNode => 0,
+            NodeData::Scalar { .. } => 1,
+            NodeData::Sequence { .. } => 2,
+            NodeData::Mapping { .. } => 3,
+        }
+    }
+}
+
+/// A collection being copied by [`Document::canonical_node()`].
+struct CanonicalFrame {
+    index: NodeId,
+    /// The id of the copy.
+    id: NodeId,
+    /// The copies of the children so far.
+    children: Vec<NodeId>,
+}
+
+/// A node being hashed by [`Node::fingerprint()`].
+struct FingerprintFrame<'a> {
+    node: &'a Node,
+    /// The id of the node, unless it is the node being fingerprinted.
+    id: Option<NodeId>,
+    /// The position of the node among the collections being hashed.
+    position: usize,
+    /// The lowest position among the collections being hashed that the hash
+    /// refers to.
+    lowest: usize,
+    /// The position of the next child.
+    next: usize,
+    fingerprint: Fingerprint,
+    /// The hashes of the pairs of a mapping, which are sorted as the order of
+    /// the pairs does not matter.
+    pair_hashes: Vec<u64>,
+    /// The hash of the key of the current pair of a mapping.
+    key_hash: u64,
+}
+
+impl<'a> FingerprintFrame<'a> {
+    fn new(node: &'a Node, id: Option<NodeId>, position: usize) -> Self {
+        let mut fingerprint = Fingerprint::new();
+        fingerprint.write_u64(node.kind_rank());
+        fingerprint.write_str(node.resolved_tag());
+        match &node.data {
+            NodeData::Scalar { value, .. } => fingerprint.write_str(value),
+            NodeData::Sequence { items, .. } => fingerprint.write_u64(items.len() as u64),
+            _ => {}
+        }
+        FingerprintFrame {
+            node,
+            id,
+            position,
+            lowest: usize::MAX,
+            next: 0,
+            fingerprint,
+            pair_hashes: Vec::new(),
+            key_hash: 0,
+        }
+    }
+
+    /// Add the hash of the last child.
+    fn add(&mut self, hash: u64) {
+        match self.node.data {
+            NodeData::Mapping { .. } if self.next % 2 == 1 => self.key_hash = hash,
+            NodeData::Mapping { .. } => {
+                let mut pair_hash = Fingerprint::new();
+                pair_hash.write_u64(self.key_hash);
+                pair_hash.write_u64(hash);
+                self.pair_hashes.push(pair_hash.finish());
+            }
+            _ => self.fingerprint.write_u64(hash),
+        }
+    }
+
+    fn finish(&mut self) -> u64 {
+        if let NodeData::Mapping { .. } = self.node.data {
+            self.pair_hashes.sort_unstable();
+            self.fingerprint.write_u64(self.pair_hashes.len() as u64);
+            for pair_hash in &self.pair_hashes {
+                self.fingerprint.write_u64(*pair_hash);
+            }
+        }
+        self.fingerprint.finish()
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike `DefaultHasher` is stable across
+/// versions of Rust.
+struct Fingerprint(u64);
+
+impl Fingerprint {
+    fn new() -> Self {
+        Fingerprint(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
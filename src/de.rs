@@ -0,0 +1,633 @@
+use alloc::borrow::Cow;
+use core::ops::Range;
+use std::collections::HashMap;
+
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::Deserialize;
+
+use crate::schema::{is_null, parse_bool, parse_float, parse_int};
+use crate::{
+    BorrowedEvent, Error, EventData, Limit, Mark, Parser, Result, ScalarStyle, BOOL_TAG, FLOAT_TAG,
+    INT_TAG, NULL_TAG, STR_TAG,
+};
+
+/// The maximum nesting depth, unless [`ParserLimits::max_depth`] is set.
+///
+/// [`ParserLimits::max_depth`]: crate::ParserLimits::max_depth
+const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// The maximum number of nodes produced by expanding aliases, unless
+/// [`ParserLimits::max_alias_expansion`] is set.
+///
+/// [`ParserLimits::max_alias_expansion`]: crate::ParserLimits::max_alias_expansion
+const DEFAULT_MAX_ALIAS_EXPANSION: usize = 1 << 20;
+
+/// Deserialize an instance of `T` from a string of YAML.
+///
+/// The input must contain at most one document. An empty input is a null
+/// value.
+pub fn from_str<'de, T: Deserialize<'de>>(input: &'de str) -> Result<T> {
+    from_slice(input.as_bytes())
+}
+
+/// Deserialize an instance of `T` from bytes of YAML.
+///
+/// See [`from_str()`].
+pub fn from_slice<'de, T: Deserialize<'de>>(input: &'de [u8]) -> Result<T> {
+    let mut parser = Parser::new();
+    parser.set_input_slice(input);
+    let mut deserializer = Deserializer::new(parser);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Deserialize an instance of `T` from a reader of YAML.
+///
+/// See [`from_str()`].
+pub fn from_reader<R: std::io::BufRead, T: DeserializeOwned>(mut reader: R) -> Result<T> {
+    let mut parser = Parser::new();
+    parser.set_input(&mut reader);
+    let mut deserializer = Deserializer::new(parser);
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// A serde deserializer that reads a YAML document from a [`Parser`].
+///
+/// Plain scalars are resolved according to the YAML 1.2 core schema, and the
+/// `!!null`, `!!bool`, `!!int`, `!!float` and `!!str` tags are respected. When
+/// the input is a slice, strings are borrowed from it where possible.
+///
+/// Aliases are expanded by replaying the events of the anchored node. The
+/// [`ParserLimits`](crate::ParserLimits) of the parser apply; in addition, the
+/// nesting depth is limited to 128 and alias expansion to about a million
+/// nodes, unless [`ParserLimits::max_depth`] and
+/// [`ParserLimits::max_alias_expansion`] are set.
+///
+/// [`ParserLimits::max_depth`]: crate::ParserLimits::max_depth
+/// [`ParserLimits::max_alias_expansion`]: crate::ParserLimits::max_alias_expansion
+pub struct Deserializer<'de> {
+    parser: Parser<'de>,
+    /// Has the DOCUMENT-START event been consumed?
+    started: bool,
+    /// The next event, if it was peeked.
+    peeked: Option<BorrowedEvent<'de>>,
+    /// The events of the anchored nodes, each recorded once.
+    recorded: Vec<Recorded<'de>>,
+    /// The range of `recorded` of each anchored node.
+    anchors: HashMap<String, Range<usize>>,
+    /// The anchored nodes whose events are being recorded.
+    recordings: Vec<Recording>,
+    /// The number of unfinished collections being recorded.
+    recording_depth: usize,
+    /// The stack of aliases whose events are being replayed.
+    replays: Vec<Replay>,
+    /// The number of nodes produced by replaying aliases.
+    expanded: usize,
+    max_expansion: usize,
+    /// The number of unfinished collections.
+    depth: usize,
+    max_depth: usize,
+}
+
+/// A recorded event of an anchored node.
+enum Recorded<'de> {
+    Event(Box<BorrowedEvent<'de>>),
+    /// An alias, resolved at the time it was recorded.
+    Alias(Range<usize>),
+}
+
+struct Recording {
+    anchor: String,
+    /// The index of the first event of the node in `recorded`.
+    start: usize,
+    /// The value of `recording_depth` before the node.
+    depth: usize,
+}
+
+struct Replay {
+    /// The events of `recorded` that are left to replay.
+    events: Range<usize>,
+    /// The mark of the alias.
+    mark: Mark,
+}
+
+impl<'de> Deserializer<'de> {
+    /// Create a deserializer that reads the next document from `parser`.
+    pub fn new(parser: Parser<'de>) -> Self {
+        let limits = parser.scanner.limits;
+        Self {
+            parser,
+            started: false,
+            peeked: None,
+            recorded: Vec::new(),
+            anchors: HashMap::new(),
+            recordings: Vec::new(),
+            recording_depth: 0,
+            replays: Vec::new(),
+            expanded: 0,
+            max_expansion: limits
+                .max_alias_expansion
+                .unwrap_or(DEFAULT_MAX_ALIAS_EXPANSION),
+            depth: 0,
+            max_depth: limits.max_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+        }
+    }
+
+    /// Check that the input has no more documents.
+    ///
+    /// Call this after deserializing a value.
+    pub fn end(&mut self) -> Result<()> {
+        let event = self.next()?;
+        match event.data {
+            EventData::DocumentEnd { .. } => {}
+            EventData::StreamEnd => return Ok(()),
+            _ => return Err(unexpected_event(&event)),
+        }
+        let event = self.next()?;
+        match event.data {
+            EventData::StreamEnd => Ok(()),
+            _ => Err(Error::data(
+                String::from("found more than one document"),
+                Some(event.start_mark),
+            )),
+        }
+    }
+
+    fn peek(&mut self) -> Result<&BorrowedEvent<'de>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.next_event()?);
+        }
+        Ok(self.peeked.as_ref().unwrap())
+    }
+
+    fn next(&mut self) -> Result<BorrowedEvent<'de>> {
+        match self.peeked.take() {
+            Some(event) => Ok(event),
+            None => self.next_event(),
+        }
+    }
+
+    /// Produce the next event, expanding aliases.
+    fn next_event(&mut self) -> Result<BorrowedEvent<'de>> {
+        if !self.started {
+            self.started = true;
+            return self.start();
+        }
+        loop {
+            if let Some(replay) = self.replays.last_mut() {
+                let Some(index) = replay.events.next() else {
+                    self.replays.pop();
+                    continue;
+                };
+                let mark = replay.mark;
+                match &self.recorded[index] {
+                    Recorded::Event(event) => {
+                        let event = BorrowedEvent::clone(event);
+                        if is_node(&event) {
+                            self.expanded += 1;
+                            if self.expanded > self.max_expansion {
+                                return Err(Error::composer(
+                                    "",
+                                    Mark::default(),
+                                    "exceeded the alias expansion limit",
+                                    mark,
+                                ));
+                            }
+                        }
+                        return Ok(event);
+                    }
+                    Recorded::Alias(events) => {
+                        let events = events.clone();
+                        self.replays.push(Replay { events, mark });
+                        continue;
+                    }
+                }
+            }
+
+            let event = self.parser.parse_borrowed()?;
+            if let EventData::Alias { anchor } = &event.data {
+                let events = self.resolve_alias(anchor, event.start_mark)?;
+                if !self.recordings.is_empty() {
+                    self.recorded.push(Recorded::Alias(events.clone()));
+                }
+                self.replays.push(Replay {
+                    events,
+                    mark: event.start_mark,
+                });
+                continue;
+            }
+            self.record(&event);
+            return Ok(event);
+        }
+    }
+
+    /// Skip the STREAM-START and DOCUMENT-START events, and produce the first
+    /// event of the document.
+    ///
+    /// An empty stream produces a null scalar.
+    fn start(&mut self) -> Result<BorrowedEvent<'de>> {
+        let mut event = self.parser.parse_borrowed()?;
+        if let EventData::StreamStart { .. } = event.data {
+            event = self.parser.parse_borrowed()?;
+        }
+        match event.data {
+            EventData::DocumentStart { .. } => self.next_event(),
+            EventData::StreamEnd => Ok(BorrowedEvent {
+                data: EventData::Scalar {
+                    anchor: None,
+                    tag: None,
+                    value: Cow::Borrowed(""),
+                    plain_implicit: true,
+                    quoted_implicit: false,
+                    style: ScalarStyle::Plain,
+                    comments: crate::Comments::default(),
+                },
+                start_mark: event.start_mark,
+                end_mark: event.end_mark,
+            }),
+            _ => Err(unexpected_event(&event)),
+        }
+    }
+
+    fn resolve_alias(&self, anchor: &str, mark: Mark) -> Result<Range<usize>> {
+        if self
+            .recordings
+            .iter()
+            .any(|recording| recording.anchor == anchor)
+        {
+            return Err(Error::composer(
+                "",
+                Mark::default(),
+                "found recursive alias",
+                mark,
+            ));
+        }
+        match self.anchors.get(anchor) {
+            Some(events) => Ok(events.clone()),
+            None => Err(Error::composer(
+                "",
+                Mark::default(),
+                "found undefined alias",
+                mark,
+            )),
+        }
+    }
+
+    /// Record the event for the anchored nodes it belongs to.
+    fn record(&mut self, event: &BorrowedEvent<'de>) {
+        if let EventData::Scalar {
+            anchor: Some(anchor),
+            ..
+        }
+        | EventData::SequenceStart {
+            anchor: Some(anchor),
+            ..
+        }
+        | EventData::MappingStart {
+            anchor: Some(anchor),
+            ..
+        } = &event.data
+        {
+            self.recordings.push(Recording {
+                anchor: String::from(&**anchor),
+                start: self.recorded.len(),
+                depth: self.recording_depth,
+            });
+        }
+        if self.recordings.is_empty() {
+            return;
+        }
+        self.recorded.push(Recorded::Event(Box::new(event.clone())));
+        match event.data {
+            EventData::SequenceStart { .. } | EventData::MappingStart { .. } => {
+                self.recording_depth += 1;
+            }
+            EventData::SequenceEnd { .. } | EventData::MappingEnd { .. } => {
+                self.recording_depth -= 1;
+            }
+            _ => {}
+        }
+        while let Some(recording) = self.recordings.last() {
+            if recording.depth != self.recording_depth {
+                break;
+            }
+            let recording = self.recordings.pop().unwrap();
+            self.anchors
+                .insert(recording.anchor, recording.start..self.recorded.len());
+        }
+    }
+
+    fn enter(&mut self, mark: Mark) -> Result<()> {
+        self.depth += 1;
+        if self.depth > self.max_depth {
+            return Err(Error::limit_exceeded(Limit::Depth, mark));
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    /// Consume the end of the sequence or mapping that a visitor has read.
+    fn end_collection(&mut self) -> Result<()> {
+        let event = self.next()?;
+        match event.data {
+            EventData::SequenceEnd { .. } | EventData::MappingEnd { .. } => {
+                self.leave();
+                Ok(())
+            }
+            _ => Err(Error::data(
+                String::from("found unexpected entries at the end of the collection"),
+                Some(event.start_mark),
+            )),
+        }
+    }
+
+    /// Skip the events of a node.
+    fn skip_node(&mut self) -> Result<()> {
+        let depth = self.depth;
+        loop {
+            let event = self.next()?;
+            match event.data {
+                EventData::SequenceStart { .. } | EventData::MappingStart { .. } => {
+                    self.enter(event.start_mark)?;
+                }
+                EventData::SequenceEnd { .. } | EventData::MappingEnd { .. } => self.leave(),
+                EventData::Scalar { .. } => {}
+                _ => return Err(unexpected_event(&event)),
+            }
+            if self.depth == depth {
+                return Ok(());
+            }
+        }
+    }
+
+    fn visit_scalar<V: Visitor<'de>>(
+        value: Cow<'de, str>,
+        tag: Option<&str>,
+        style: ScalarStyle,
+        visitor: V,
+    ) -> Result<V::Value> {
+        match resolve(&value, tag, style) {
+            Some(Scalar::Null) => visitor.visit_unit(),
+            Some(Scalar::Bool(value)) => visitor.visit_bool(value),
+            Some(Scalar::Int(value)) => {
+                if let Ok(value) = i64::try_from(value) {
+                    visitor.visit_i64(value)
+                } else if let Ok(value) = u64::try_from(value) {
+                    visitor.visit_u64(value)
+                } else {
+                    visitor.visit_i128(value)
+                }
+            }
+            Some(Scalar::Float(value)) => visitor.visit_f64(value),
+            Some(Scalar::Str) => visit_str(value, visitor),
+            None => Err(Error::data(
+                alloc::format!("invalid value for tag {}", tag.unwrap_or_default()),
+                None,
+            )),
+        }
+    }
+}
+
+fn visit_str<'de, V: Visitor<'de>>(value: Cow<'de, str>, visitor: V) -> Result<V::Value> {
+    match value {
+        Cow::Borrowed(value) => visitor.visit_borrowed_str(value),
+        Cow::Owned(value) => visitor.visit_string(value),
+    }
+}
+
+/// The value of a scalar.
+enum Scalar {
+    Null,
+    Bool(bool),
+    Int(i128),
+    Float(f64),
+    Str,
+}
+
+/// Resolve a scalar according to its tag, or to the core schema if it is a
+/// plain scalar without a tag.
+///
+/// Returns `None` if the value is invalid for its tag.
+fn resolve(value: &str, tag: Option<&str>, style: ScalarStyle) -> Option<Scalar> {
+    match tag {
+        Some(NULL_TAG) => is_null(value).then_some(Scalar::Null),
+        Some(BOOL_TAG) => parse_bool(value).map(Scalar::Bool),
+        Some(INT_TAG) => parse_int(value).map(Scalar::Int),
+        Some(FLOAT_TAG) => parse_float(value).map(Scalar::Float),
+        Some(STR_TAG | "!") => Some(Scalar::Str),
+        _ if style != ScalarStyle::Plain => Some(Scalar::Str),
+        _ => Some(if is_null(value) {
+            Scalar::Null
+        } else if let Some(value) = parse_bool(value) {
+            Scalar::Bool(value)
+        } else if let Some(value) = parse_int(value) {
+            Scalar::Int(value)
+        } else if let Some(value) = parse_float(value) {
+            Scalar::Float(value)
+        } else {
+            Scalar::Str
+        }),
+    }
+}
+
+fn is_node(event: &BorrowedEvent) -> bool {
+    matches!(
+        event.data,
+        EventData::Scalar { .. } | EventData::SequenceStart { .. } | EventData::MappingStart { .. }
+    )
+}
+
+fn unexpected_event(event: &BorrowedEvent) -> Error {
+    Error::data(
+        alloc::format!("unexpected event {:?}", event.data),
+        Some(event.start_mark),
+    )
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let event = self.next()?;
+        let mark = event.start_mark;
+        let result = match event.data {
+            EventData::Scalar {
+                value, tag, style, ..
+            } => Deserializer::visit_scalar(value, tag.as_deref(), style, visitor),
+            EventData::SequenceStart { .. } => {
+                self.enter(mark)?;
+                visitor
+                    .visit_seq(&mut *self)
+                    .and_then(|value| self.end_collection().map(|()| value))
+            }
+            EventData::MappingStart { .. } => {
+                self.enter(mark)?;
+                visitor
+                    .visit_map(&mut *self)
+                    .and_then(|value| self.end_collection().map(|()| value))
+            }
+            _ => Err(unexpected_event(&event)),
+        };
+        result.map_err(|err| err.with_mark(mark))
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if let EventData::Scalar { .. } = self.peek()?.data {
+            let event = self.next()?;
+            let EventData::Scalar { value, .. } = event.data else {
+                unreachable!()
+            };
+            visit_str(value, visitor).map_err(|err| err.with_mark(event.start_mark))
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let event = self.peek()?;
+        let mark = event.start_mark;
+        if let EventData::Scalar {
+            value, tag, style, ..
+        } = &event.data
+        {
+            if let Some(Scalar::Null) = resolve(value, tag.as_deref(), *style) {
+                self.next()?;
+                return visitor
+                    .visit_none::<Error>()
+                    .map_err(|err| err.with_mark(mark));
+            }
+        }
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let event = self.next()?;
+        let mark = event.start_mark;
+        let result = match event.data {
+            EventData::Scalar {
+                value: Cow::Borrowed(value),
+                ..
+            } => visitor.visit_enum(BorrowedStrDeserializer::new(value)),
+            EventData::Scalar {
+                value: Cow::Owned(value),
+                ..
+            } => visitor.visit_enum(value.into_deserializer()),
+            EventData::MappingStart { .. } => {
+                self.enter(mark)?;
+                visitor
+                    .visit_enum(&mut *self)
+                    .and_then(|value| self.end_collection().map(|()| value))
+            }
+            _ => Err(Error::data(
+                String::from("expected a string or a mapping with a single key for an enum"),
+                None,
+            )),
+        };
+        result.map_err(|err| err.with_mark(mark))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.skip_node()?;
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct
+    }
+}
+
+impl<'de> SeqAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if let EventData::SequenceEnd { .. } = self.peek()?.data {
+            return Ok(None);
+        }
+        seed.deserialize(&mut **self).map(Some)
+    }
+}
+
+impl<'de> MapAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if let EventData::MappingEnd { .. } = self.peek()?.data {
+            return Ok(None);
+        }
+        seed.deserialize(&mut **self).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut **self)
+    }
+}
+
+impl<'de> EnumAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = seed.deserialize(&mut *self)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
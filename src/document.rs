@@ -1,7 +1,17 @@
+use alloc::borrow::Cow;
+use core::num::NonZeroU32;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::schema::{
+    parse_bool, parse_float, parse_int, parse_yaml11_bool, parse_yaml11_float, parse_yaml11_int,
+};
 use crate::{
-    AliasData, Anchors, Emitter, Error, Event, EventData, MappingStyle, Mark, Parser, Result,
-    ScalarStyle, SequenceStyle, TagDirective, VersionDirective, DEFAULT_MAPPING_TAG,
-    DEFAULT_SCALAR_TAG, DEFAULT_SEQUENCE_TAG,
+    AliasData, Anchors, Comments, Emitter, Error, Event, EventData, MappingStyle, Mark, Parser,
+    Result, ScalarStyle, Schema, SequenceStyle, TagDirective, VersionDirective, BOOL_TAG,
+    DEFAULT_MAPPING_TAG, DEFAULT_SCALAR_TAG, DEFAULT_SEQUENCE_TAG, FLOAT_TAG, INT_TAG, MERGE_TAG,
+    NULL_TAG, STR_TAG,
 };
 
 /// The document structure.
@@ -32,6 +42,16 @@ pub struct Node {
     pub data: NodeData,
     /// The node tag.
     pub tag: Option<String>,
+    /// The schema that was used to load the node.
+    ///
+    /// It determines how the typed accessors such as [`Node::as_i64()`] read
+    /// the value of a scalar.
+    pub schema: Schema,
+    /// The anchor the node was defined with, if any.
+    ///
+    /// [`Document::dump()`] reuses it for nodes that need an anchor, as long as
+    /// no other such node carries the same name.
+    pub anchor: Option<String>,
     /// The beginning of the node.
     pub start_mark: Mark,
     /// The end of the node.
@@ -68,16 +88,228 @@ pub enum NodeData {
 }
 
 /// An element of a sequence node.
-pub type NodeItem = i32;
+pub type NodeItem = NodeId;
 
 /// An element of a mapping node.
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Copy, Clone, Debug)]
 #[non_exhaustive]
 pub struct NodePair {
     /// The key of the element.
-    pub key: i32,
+    pub key: NodeId,
     /// The value of the element.
-    pub value: i32,
+    pub value: NodeId,
+}
+
+/// A difference between two documents, found by [`Document::diff()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Difference {
+    /// The kind of difference.
+    pub kind: DifferenceKind,
+    /// The path of the node from the root node, as in [`Document::get_path()`].
+    pub path: Vec<String>,
+    /// The position of the node in the first document, or of its parent
+    /// collection if the node was added.
+    pub old_mark: Mark,
+    /// The position of the node in the second document, or of its parent
+    /// collection if the node was removed.
+    pub new_mark: Mark,
+}
+
+/// The kinds of differences between two documents.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DifferenceKind {
+    /// The node is only in the second document.
+    Added,
+    /// The node is only in the first document.
+    Removed,
+    /// The node has a different tag or value in the second document.
+    Changed,
+}
+
+/// How [`Document::merge_from()`] merges sequences.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// A sequence replaces the sequence it is merged into.
+    #[default]
+    Replace,
+    /// The items of a sequence are appended to the sequence it is merged into.
+    Append,
+}
+
+/// The id of a node in a [`Document`].
+///
+/// Ids are assigned in the order in which nodes are added to the document,
+/// starting with the root node.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct NodeId(NonZeroU32);
+
+impl NodeId {
+    /// The id of the node at position `index` in [`Document::nodes`].
+    pub(crate) fn from_index(index: usize) -> Self {
+        let id = u32::try_from(index + 1)
+            .ok()
+            .and_then(NonZeroU32::new)
+            .expect("too many nodes in the document");
+        Self(id)
+    }
+
+    /// The position of the node in [`Document::nodes`].
+    pub(crate) fn index(self) -> usize {
+        self.0.get() as usize - 1
+    }
+}
+
+/// Unescape a reference token of a JSON Pointer.
+fn unescape_pointer_token(token: &str) -> Option<Cow<'_, str>> {
+    if !token.contains('~') {
+        return Some(Cow::Borrowed(token));
+    }
+    let mut unescaped = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(ch) = chars.next() {
+        if ch == '~' {
+            match chars.next() {
+                Some('0') => unescaped.push('~'),
+                Some('1') => unescaped.push('/'),
+                _ => return None,
+            }
+        } else {
+            unescaped.push(ch);
+        }
+    }
+    Some(Cow::Owned(unescaped))
+}
+
+/// Parse the index of a sequence item: `0`, or a decimal number without
+/// leading zeros.
+fn parse_array_index(key: &str) -> Option<usize> {
+    if key.is_empty()
+        || key.len() > 1 && key.starts_with('0')
+        || !key.bytes().all(|byte| byte.is_ascii_digit())
+    {
+        return None;
+    }
+    key.parse().ok()
+}
+
+/// A collection that is being loaded.
+struct Parent {
+    /// The collection node.
+    id: NodeId,
+    /// The key of the current pair of a mapping, until its value is loaded.
+    key: Option<NodeId>,
+}
+
+impl Node {
+    /// Hash the tag and the kind of the node, and the value of a scalar or the
+    /// size of a collection.
+    fn hash_kind(&self, hasher: &mut DefaultHasher) {
+        self.tag.hash(hasher);
+        core::mem::discriminant(&self.data).hash(hasher);
+        match &self.data {
+            NodeData::NoNode => {}
+            NodeData::Scalar { value, .. } => value.hash(hasher),
+            NodeData::Sequence { items, .. } => items.len().hash(hasher),
+            NodeData::Mapping { pairs, .. } => pairs.len().hash(hasher),
+        }
+    }
+
+    /// The child at `position` of a collection node: the items of a sequence,
+    /// or the keys and values of the pairs of a mapping, in turn.
+    pub(crate) fn child(&self, position: usize) -> Option<NodeId> {
+        match &self.data {
+            NodeData::Sequence { items, .. } => items.get(position).copied(),
+            NodeData::Mapping { pairs, .. } => pairs.get(position / 2).map(|pair| {
+                if position % 2 == 0 {
+                    pair.key
+                } else {
+                    pair.value
+                }
+            }),
+            _ => None,
+        }
+    }
+
+    /// The value of a scalar node with the tag `tag`.
+    fn scalar_value(&self, tag: &str) -> Option<&str> {
+        match &self.data {
+            NodeData::Scalar { value, .. } if self.tag.as_deref() == Some(tag) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value of a `!!str` scalar node.
+    ///
+    /// A scalar node without a tag is a string as well.
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.data {
+            NodeData::Scalar { value, .. }
+                if self.tag.is_none() || self.tag.as_deref() == Some(STR_TAG) =>
+            {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Is this a `!!null` scalar node?
+    pub fn is_null(&self) -> bool {
+        self.scalar_value(NULL_TAG).is_some()
+    }
+
+    /// The value of a `!!bool` scalar node.
+    ///
+    /// Accepts the YAML 1.2 values `true` and `false` in any of their three
+    /// spellings. If the node was loaded with [`Schema::Yaml11`], accepts the
+    /// YAML 1.1 booleans instead, such as `yes` and `off`.
+    pub fn as_bool(&self) -> Option<bool> {
+        let value = self.scalar_value(BOOL_TAG)?;
+        match self.schema {
+            Schema::Yaml11 => parse_yaml11_bool(value),
+            _ => parse_bool(value),
+        }
+    }
+
+    /// The value of an `!!int` scalar node, if it fits in an `i64`.
+    ///
+    /// Accepts decimal integers with an optional sign, and octal (`0o`) and
+    /// hexadecimal (`0x`) integers, as in YAML 1.2. If the node was loaded with
+    /// [`Schema::Yaml11`], accepts the YAML 1.1 integers instead, such as
+    /// `0b11`, `012` (octal), `1_000` and `1:30` (sexagesimal).
+    pub fn as_i64(&self) -> Option<i64> {
+        i64::try_from(self.int_value()?).ok()
+    }
+
+    /// The value of an `!!int` scalar node, if it fits in a `u64`.
+    ///
+    /// See [`Node::as_i64()`].
+    pub fn as_u64(&self) -> Option<u64> {
+        u64::try_from(self.int_value()?).ok()
+    }
+
+    /// The value of a `!!float` scalar node.
+    ///
+    /// Accepts the YAML 1.2 float syntax, `.inf`, `-.inf` and `.nan`. If the
+    /// node was loaded with [`Schema::Yaml11`], also accepts the YAML 1.1
+    /// floats, such as `1_000.5` and `1:30.5` (sexagesimal).
+    pub fn as_f64(&self) -> Option<f64> {
+        let value = self.scalar_value(FLOAT_TAG)?;
+        match self.schema {
+            // YAML 1.1 floats need a decimal point, which explicitly tagged
+            // values such as `!!float 2` often lack.
+            Schema::Yaml11 => parse_yaml11_float(value).or_else(|| parse_float(value)),
+            _ => parse_float(value),
+        }
+    }
+
+    fn int_value(&self) -> Option<i128> {
+        let value = self.scalar_value(INT_TAG)?;
+        match self.schema {
+            Schema::Yaml11 => parse_yaml11_int(value),
+            _ => parse_int(value),
+        }
+    }
 }
 
 impl Document {
@@ -104,16 +336,18 @@ impl Document {
 
     /// Get a node of a YAML document.
     ///
-    /// Returns the node object or `None` if `index` is out of range.
-    pub fn get_node_mut(&mut self, index: i32) -> Option<&mut Node> {
-        self.nodes.get_mut(index as usize - 1)
+    /// Returns the node object or `None` if `id` is not a node of the
+    /// document.
+    pub fn get_node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes.get_mut(id.index())
     }
 
     /// Get a node of a YAML document.
     ///
-    /// Returns the node object or `None` if `index` is out of range.
-    pub fn get_node(&self, index: i32) -> Option<&Node> {
-        self.nodes.get(index as usize - 1)
+    /// Returns the node object or `None` if `id` is not a node of the
+    /// document.
+    pub fn get_node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.index())
     }
 
     /// Get the root of a YAML document node.
@@ -127,13 +361,86 @@ impl Document {
         self.nodes.get_mut(0)
     }
 
+    /// Get the id of the node at `path` from the root node.
+    ///
+    /// Each element of the path is the key of a mapping pair, compared to the
+    /// value of scalar keys, or the index of a sequence item. An empty path
+    /// is the root node.
+    ///
+    /// Returns `None` if there is no such node.
+    pub fn get_path_id(&self, path: &[&str]) -> Option<NodeId> {
+        self.lookup(path.iter().copied())
+    }
+
+    /// Get the node at `path` from the root node.
+    ///
+    /// See [`Document::get_path_id()`].
+    pub fn get_path(&self, path: &[&str]) -> Option<&Node> {
+        self.get_node(self.get_path_id(path)?)
+    }
+
+    /// Get the node at `path` from the root node.
+    ///
+    /// See [`Document::get_path_id()`].
+    pub fn get_path_mut(&mut self, path: &[&str]) -> Option<&mut Node> {
+        self.get_node_mut(self.get_path_id(path)?)
+    }
+
+    /// Get the id of the node at a JSON Pointer (RFC 6901), such as
+    /// `/spec/containers/0/image`.
+    ///
+    /// The reference tokens are unescaped (`~1` to `/` and `~0` to `~`), and
+    /// looked up as in [`Document::get_path_id()`].
+    ///
+    /// Returns `None` if the pointer is invalid or there is no such node.
+    pub fn get_pointer_id(&self, pointer: &str) -> Option<NodeId> {
+        if pointer.is_empty() {
+            return self.lookup(core::iter::empty::<&str>());
+        }
+        let tokens = pointer
+            .strip_prefix('/')?
+            .split('/')
+            .map(unescape_pointer_token)
+            .collect::<Option<Vec<_>>>()?;
+        self.lookup(tokens.iter().map(AsRef::as_ref))
+    }
+
+    /// Get the node at a JSON Pointer (RFC 6901).
+    ///
+    /// See [`Document::get_pointer_id()`].
+    pub fn get_pointer(&self, pointer: &str) -> Option<&Node> {
+        self.get_node(self.get_pointer_id(pointer)?)
+    }
+
+    /// Get the node at a JSON Pointer (RFC 6901).
+    ///
+    /// See [`Document::get_pointer_id()`].
+    pub fn get_pointer_mut(&mut self, pointer: &str) -> Option<&mut Node> {
+        self.get_node_mut(self.get_pointer_id(pointer)?)
+    }
+
+    fn lookup<'a>(&self, path: impl IntoIterator<Item = &'a str>) -> Option<NodeId> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut id = NodeId::from_index(0);
+        for key in path {
+            id = match &self.nodes[id.index()].data {
+                NodeData::Sequence { items, .. } => *items.get(parse_array_index(key)?)?,
+                NodeData::Mapping { pairs, .. } => pairs[self.key_position(pairs, key)?].value,
+                _ => return None,
+            };
+        }
+        Some(id)
+    }
+
     /// Create a SCALAR node and attach it to the document.
     ///
     /// The `style` argument may be ignored by the emitter.
     ///
-    /// Returns the node id or 0 on error.
+    /// Returns the node id.
     #[must_use]
-    pub fn add_scalar(&mut self, tag: Option<&str>, value: &str, style: ScalarStyle) -> i32 {
+    pub fn add_scalar(&mut self, tag: Option<&str>, value: &str, style: ScalarStyle) -> NodeId {
         let mark = Mark {
             index: 0_u64,
             line: 0_u64,
@@ -148,20 +455,22 @@ impl Document {
                 style,
             },
             tag: Some(tag_copy),
+            schema: Schema::default(),
+            anchor: None,
             start_mark: mark,
             end_mark: mark,
         };
         self.nodes.push(node);
-        self.nodes.len() as i32
+        NodeId::from_index(self.nodes.len() - 1)
     }
 
     /// Create a SEQUENCE node and attach it to the document.
     ///
     /// The `style` argument may be ignored by the emitter.
     ///
-    /// Returns the node id, which is a nonzero integer.
+    /// Returns the node id.
     #[must_use]
-    pub fn add_sequence(&mut self, tag: Option<&str>, style: SequenceStyle) -> i32 {
+    pub fn add_sequence(&mut self, tag: Option<&str>, style: SequenceStyle) -> NodeId {
         let mark = Mark {
             index: 0_u64,
             line: 0_u64,
@@ -174,20 +483,22 @@ impl Document {
         let node = Node {
             data: NodeData::Sequence { items, style },
             tag: Some(tag_copy),
+            schema: Schema::default(),
+            anchor: None,
             start_mark: mark,
             end_mark: mark,
         };
         self.nodes.push(node);
-        self.nodes.len() as i32
+        NodeId::from_index(self.nodes.len() - 1)
     }
 
     /// Create a MAPPING node and attach it to the document.
     ///
     /// The `style` argument may be ignored by the emitter.
     ///
-    /// Returns the node id, which is a nonzero integer.
+    /// Returns the node id.
     #[must_use]
-    pub fn add_mapping(&mut self, tag: Option<&str>, style: MappingStyle) -> i32 {
+    pub fn add_mapping(&mut self, tag: Option<&str>, style: MappingStyle) -> NodeId {
         let mark = Mark {
             index: 0_u64,
             line: 0_u64,
@@ -200,45 +511,627 @@ impl Document {
         let node = Node {
             data: NodeData::Mapping { pairs, style },
             tag: Some(tag_copy),
+            schema: Schema::default(),
+            anchor: None,
             start_mark: mark,
             end_mark: mark,
         };
 
         self.nodes.push(node);
-        self.nodes.len() as i32
+        NodeId::from_index(self.nodes.len() - 1)
     }
 
     /// Add an item to a SEQUENCE node.
-    pub fn append_sequence_item(&mut self, sequence: i32, item: i32) {
-        assert!(sequence > 0 && sequence as usize - 1 < self.nodes.len());
+    pub fn append_sequence_item(&mut self, sequence: NodeId, item: NodeId) {
+        assert!(sequence.index() < self.nodes.len());
         assert!(matches!(
-            &self.nodes[sequence as usize - 1].data,
+            &self.nodes[sequence.index()].data,
             NodeData::Sequence { .. }
         ));
-        assert!(item > 0 && item as usize - 1 < self.nodes.len());
-        if let NodeData::Sequence { ref mut items, .. } =
-            &mut self.nodes[sequence as usize - 1].data
-        {
+        assert!(item.index() < self.nodes.len());
+        if let NodeData::Sequence { ref mut items, .. } = &mut self.nodes[sequence.index()].data {
             items.push(item);
         }
     }
 
     /// Add a pair of a key and a value to a MAPPING node.
-    pub fn yaml_document_append_mapping_pair(&mut self, mapping: i32, key: i32, value: i32) {
-        assert!(mapping > 0 && mapping as usize - 1 < self.nodes.len());
+    pub fn yaml_document_append_mapping_pair(
+        &mut self,
+        mapping: NodeId,
+        key: NodeId,
+        value: NodeId,
+    ) {
+        assert!(mapping.index() < self.nodes.len());
         assert!(matches!(
-            &self.nodes[mapping as usize - 1].data,
+            &self.nodes[mapping.index()].data,
             NodeData::Mapping { .. }
         ));
-        assert!(key > 0 && key as usize - 1 < self.nodes.len());
-        assert!(value > 0 && value as usize - 1 < self.nodes.len());
+        assert!(key.index() < self.nodes.len());
+        assert!(value.index() < self.nodes.len());
         let pair = NodePair { key, value };
-        if let NodeData::Mapping { ref mut pairs, .. } = &mut self.nodes[mapping as usize - 1].data
-        {
+        if let NodeData::Mapping { ref mut pairs, .. } = &mut self.nodes[mapping.index()].data {
             pairs.push(pair);
         }
     }
 
+    /// Insert an item into a SEQUENCE node at position `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sequence` is not a SEQUENCE node, or if `index` is greater
+    /// than the number of items.
+    pub fn insert_sequence_item(&mut self, sequence: NodeId, index: usize, item: NodeId) {
+        assert!(item.index() < self.nodes.len());
+        self.sequence_items_mut(sequence).insert(index, item);
+    }
+
+    /// Remove the item at position `index` from a SEQUENCE node.
+    ///
+    /// Returns the id of the removed item, or `None` if `index` is out of
+    /// range. The item stays in the document until [`Document::compact()`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sequence` is not a SEQUENCE node.
+    pub fn remove_sequence_item(&mut self, sequence: NodeId, index: usize) -> Option<NodeId> {
+        let items = self.sequence_items_mut(sequence);
+        (index < items.len()).then(|| items.remove(index))
+    }
+
+    /// Insert a pair of a key and a value into a MAPPING node at position
+    /// `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mapping` is not a MAPPING node, or if `index` is greater
+    /// than the number of pairs.
+    pub fn insert_mapping_pair(
+        &mut self,
+        mapping: NodeId,
+        index: usize,
+        key: NodeId,
+        value: NodeId,
+    ) {
+        assert!(key.index() < self.nodes.len());
+        assert!(value.index() < self.nodes.len());
+        self.mapping_pairs_mut(mapping)
+            .insert(index, NodePair { key, value });
+    }
+
+    /// Remove the first pair with a scalar key of value `key` from a MAPPING
+    /// node.
+    ///
+    /// Returns the removed pair, or `None` if there is no such key. The key
+    /// and the value stay in the document until [`Document::compact()`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `mapping` is not a MAPPING node.
+    pub fn remove_mapping_pair(&mut self, mapping: NodeId, key: &str) -> Option<NodePair> {
+        let NodeData::Mapping { pairs, .. } = &self.nodes[mapping.index()].data else {
+            panic!("expected a mapping node")
+        };
+        let position = self.key_position(pairs, key)?;
+        Some(self.mapping_pairs_mut(mapping).remove(position))
+    }
+
+    /// Replace the node `id` with `node`, and return the previous node.
+    ///
+    /// Every sequence item, mapping key, and mapping value that refers to
+    /// `id` refers to the new node.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is not a node of the document.
+    pub fn replace_node(&mut self, id: NodeId, node: Node) -> Node {
+        assert!(id.index() < self.nodes.len());
+        core::mem::replace(&mut self.nodes[id.index()], node)
+    }
+
+    /// Remove the nodes that cannot be reached from the root node.
+    ///
+    /// The remaining nodes keep their order, so the root node stays the first
+    /// node. Their ids change, and the returned map gives the new id of each
+    /// remaining node.
+    pub fn compact(&mut self) -> HashMap<NodeId, NodeId> {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(NodeId::from_index(0));
+        }
+        while let Some(id) = stack.pop() {
+            if core::mem::replace(&mut reachable[id.index()], true) {
+                continue;
+            }
+            match &self.nodes[id.index()].data {
+                NodeData::Sequence { items, .. } => stack.extend(items),
+                NodeData::Mapping { pairs, .. } => {
+                    stack.extend(pairs.iter().flat_map(|pair| [pair.key, pair.value]));
+                }
+                _ => {}
+            }
+        }
+
+        let mut remap = HashMap::new();
+        for (index, _) in reachable
+            .iter()
+            .enumerate()
+            .filter(|(_, reachable)| **reachable)
+        {
+            remap.insert(NodeId::from_index(index), NodeId::from_index(remap.len()));
+        }
+        let mut index = 0;
+        self.nodes.retain(|_| {
+            index += 1;
+            reachable[index - 1]
+        });
+        for node in &mut self.nodes {
+            match &mut node.data {
+                NodeData::Sequence { items, .. } => {
+                    for item in items {
+                        *item = remap[item];
+                    }
+                }
+                NodeData::Mapping { pairs, .. } => {
+                    for pair in pairs {
+                        pair.key = remap[&pair.key];
+                        pair.value = remap[&pair.value];
+                    }
+                }
+                _ => {}
+            }
+        }
+        remap
+    }
+
+    /// Merge the document `other` into this document.
+    ///
+    /// Mappings are merged deeply: the pairs of `other` whose keys are not in
+    /// this document are added, and the values of the others are merged. How
+    /// sequences are merged depends on `strategy`. Any other value of `other`
+    /// replaces the value of the pair in this document, which may leave
+    /// unreachable nodes behind (see [`Document::compact()`]). Aliases of the
+    /// replaced value elsewhere keep referring to it.
+    ///
+    /// The nodes of `other` are copied into this document, and keep their
+    /// marks. The returned map gives the id of the copy of each copied node,
+    /// which tells the nodes that came from `other` apart. A node of `other`
+    /// is merged at most once into each node of this document, so recursive
+    /// aliases end the merge instead of repeating it.
+    pub fn merge_from(
+        &mut self,
+        other: &Document,
+        strategy: MergeStrategy,
+    ) -> HashMap<NodeId, NodeId> {
+        let mut copied = HashMap::new();
+        if !other.nodes.is_empty() {
+            let root = NodeId::from_index(0);
+            if self.nodes.is_empty() {
+                self.copy_node(other, root, &mut copied);
+            } else if self.merges_into(root, other, root, strategy) {
+                let mut merged = HashSet::new();
+                self.merge_node(root, other, root, strategy, &mut copied, &mut merged);
+            } else {
+                // The root node has to stay the first node, so it is replaced in
+                // place.
+                let anchor = self.nodes[0].anchor.take();
+                copied.insert(root, root);
+                self.copy_node_into(other, root, root, &mut copied);
+                let node = &mut self.nodes[0];
+                node.anchor = node.anchor.take().or(anchor);
+            }
+        }
+        copied
+    }
+
+    fn merge_node(
+        &mut self,
+        target: NodeId,
+        other: &Document,
+        source: NodeId,
+        strategy: MergeStrategy,
+        copied: &mut HashMap<NodeId, NodeId>,
+        merged: &mut HashSet<(NodeId, NodeId)>,
+    ) {
+        if !merged.insert((target, source)) {
+            return;
+        }
+        match (
+            &self.nodes[target.index()].data,
+            &other.nodes[source.index()].data,
+        ) {
+            (NodeData::Mapping { .. }, NodeData::Mapping { pairs, .. }) => {
+                for pair in pairs {
+                    let NodeData::Mapping {
+                        pairs: existing, ..
+                    } = &self.nodes[target.index()].data
+                    else {
+                        unreachable!()
+                    };
+                    // Merging changes this document, so what is known about
+                    // its nodes only holds for one lookup.
+                    let mut comparison = Comparison::default();
+                    let matching = existing.iter().position(|existing| {
+                        self.nodes_equal(existing.key, other, pair.key, &mut comparison)
+                    });
+                    if let Some(position) = matching {
+                        let value = existing[position].value;
+                        if self.merges_into(value, other, pair.value, strategy) {
+                            self.merge_node(value, other, pair.value, strategy, copied, merged);
+                        } else {
+                            // Replace the value of this pair only, as aliases
+                            // elsewhere may refer to the same node. The copy
+                            // keeps the anchor of the value unless it has its
+                            // own.
+                            let copy = self.copy_node(other, pair.value, copied);
+                            if self.nodes[copy.index()].anchor.is_none() {
+                                let anchor = self.nodes[value.index()].anchor.clone();
+                                self.nodes[copy.index()].anchor = anchor;
+                            }
+                            self.mapping_pairs_mut(target)[position].value = copy;
+                        }
+                    } else {
+                        let key = self.copy_node(other, pair.key, copied);
+                        let value = self.copy_node(other, pair.value, copied);
+                        self.mapping_pairs_mut(target).push(NodePair { key, value });
+                    }
+                }
+            }
+            (NodeData::Sequence { .. }, NodeData::Sequence { items, .. })
+                if strategy == MergeStrategy::Append =>
+            {
+                for item in items {
+                    let item = self.copy_node(other, *item, copied);
+                    self.sequence_items_mut(target).push(item);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Is the node `source` of `other` merged into the node `target`, rather
+    /// than replacing it?
+    fn merges_into(
+        &self,
+        target: NodeId,
+        other: &Document,
+        source: NodeId,
+        strategy: MergeStrategy,
+    ) -> bool {
+        match (
+            &self.nodes[target.index()].data,
+            &other.nodes[source.index()].data,
+        ) {
+            (NodeData::Mapping { .. }, NodeData::Mapping { .. }) => true,
+            (NodeData::Sequence { .. }, NodeData::Sequence { .. }) => {
+                strategy == MergeStrategy::Append
+            }
+            _ => false,
+        }
+    }
+
+    fn copy_node(
+        &mut self,
+        other: &Document,
+        source: NodeId,
+        copied: &mut HashMap<NodeId, NodeId>,
+    ) -> NodeId {
+        if let Some(id) = copied.get(&source) {
+            return *id;
+        }
+        self.nodes.push(Node::default());
+        let id = NodeId::from_index(self.nodes.len() - 1);
+        copied.insert(source, id);
+        self.copy_node_into(other, source, id, copied);
+        id
+    }
+
+    fn copy_node_into(
+        &mut self,
+        other: &Document,
+        source: NodeId,
+        target: NodeId,
+        copied: &mut HashMap<NodeId, NodeId>,
+    ) {
+        let node = &other.nodes[source.index()];
+        let data = match &node.data {
+            NodeData::Sequence { items, style } => NodeData::Sequence {
+                items: items
+                    .iter()
+                    .map(|item| self.copy_node(other, *item, copied))
+                    .collect(),
+                style: *style,
+            },
+            NodeData::Mapping { pairs, style } => NodeData::Mapping {
+                pairs: pairs
+                    .iter()
+                    .map(|pair| NodePair {
+                        key: self.copy_node(other, pair.key, copied),
+                        value: self.copy_node(other, pair.value, copied),
+                    })
+                    .collect(),
+                style: *style,
+            },
+            data => data.clone(),
+        };
+        self.nodes[target.index()] = Node {
+            data,
+            tag: node.tag.clone(),
+            schema: node.schema,
+            anchor: node.anchor.clone(),
+            start_mark: node.start_mark,
+            end_mark: node.end_mark,
+        };
+    }
+
+    /// The position of the first pair in `pairs` with a scalar key of value
+    /// `key`.
+    fn key_position(&self, pairs: &[NodePair], key: &str) -> Option<usize> {
+        pairs.iter().position(|pair| {
+            matches!(
+                &self.nodes[pair.key.index()].data,
+                NodeData::Scalar { value, .. } if value == key
+            )
+        })
+    }
+
+    fn sequence_items_mut(&mut self, sequence: NodeId) -> &mut Vec<NodeItem> {
+        match &mut self.nodes[sequence.index()].data {
+            NodeData::Sequence { items, .. } => items,
+            _ => panic!("expected a sequence node"),
+        }
+    }
+
+    fn mapping_pairs_mut(&mut self, mapping: NodeId) -> &mut Vec<NodePair> {
+        match &mut self.nodes[mapping.index()].data {
+            NodeData::Mapping { pairs, .. } => pairs,
+            _ => panic!("expected a mapping node"),
+        }
+    }
+
+    /// Check if two documents have the same content.
+    ///
+    /// Nodes are equal if their resolved tags and their values are equal.
+    /// Styles, marks and anchors are ignored, aliases are followed, and the
+    /// order of mapping pairs does not matter. Each pair of a mapping is equal
+    /// to one pair of the other mapping at most.
+    pub fn semantic_eq(&self, other: &Document) -> bool {
+        match (self.nodes.is_empty(), other.nodes.is_empty()) {
+            (false, false) => {
+                let root = NodeId::from_index(0);
+                self.nodes_equal(root, other, root, &mut Comparison::default())
+            }
+            (empty, other_empty) => empty == other_empty,
+        }
+    }
+
+    /// List the differences between this document and `other`.
+    ///
+    /// Nodes are compared as in [`Document::semantic_eq()`]. Sequence items
+    /// are matched by index and mapping pairs by key, one to one. The
+    /// differences are listed in document order, and are empty if the
+    /// documents are semantically equal.
+    pub fn diff(&self, other: &Document) -> Vec<Difference> {
+        let mut differences = Vec::new();
+        match (self.nodes.first(), other.nodes.first()) {
+            (Some(_), Some(_)) => {
+                let root = NodeId::from_index(0);
+                self.diff_node(root, other, root, &mut differences);
+            }
+            (Some(node), None) => differences.push(Difference {
+                kind: DifferenceKind::Removed,
+                path: Vec::new(),
+                old_mark: node.start_mark,
+                new_mark: other.start_mark,
+            }),
+            (None, Some(node)) => differences.push(Difference {
+                kind: DifferenceKind::Added,
+                path: Vec::new(),
+                old_mark: self.start_mark,
+                new_mark: node.start_mark,
+            }),
+            (None, None) => {}
+        }
+        differences
+    }
+
+    /// Add the differences between the node `a` of this document and the node
+    /// `b` of `other` to `differences`.
+    ///
+    /// Collections are walked with an explicit stack, so that deeply nested
+    /// nodes cannot overflow the call stack. `visiting` holds the pairs of
+    /// nodes being compared, so that recursive aliases are only reported once.
+    fn diff_node(&self, a: NodeId, other: &Document, b: NodeId, differences: &mut Vec<Difference>) {
+        let mut path = Vec::new();
+        let mut visiting = HashSet::new();
+        let mut comparison = Comparison::default();
+        let mut tasks = vec![DiffTask::Compare { a, b, key: None }];
+        while let Some(task) = tasks.pop() {
+            let (a, b, key) = match task {
+                DiffTask::Compare { a, b, key } => (a, b, key),
+                DiffTask::Report(difference) => {
+                    differences.push(difference);
+                    continue;
+                }
+                DiffTask::Leave { a, b, key } => {
+                    visiting.remove(&(a, b));
+                    if key {
+                        path.pop();
+                    }
+                    continue;
+                }
+            };
+            let has_key = key.is_some();
+            path.extend(key);
+            if visiting.contains(&(a, b)) || self.nodes_equal(a, other, b, &mut comparison) {
+                if has_key {
+                    path.pop();
+                }
+                continue;
+            }
+            let (node_a, node_b) = (&self.nodes[a.index()], &other.nodes[b.index()]);
+            let changed = |path: &Vec<String>| Difference {
+                kind: DifferenceKind::Changed,
+                path: path.clone(),
+                old_mark: node_a.start_mark,
+                new_mark: node_b.start_mark,
+            };
+            if node_a.tag != node_b.tag {
+                differences.push(changed(&path));
+                if has_key {
+                    path.pop();
+                }
+                continue;
+            }
+            visiting.insert((a, b));
+            tasks.push(DiffTask::Leave { a, b, key: has_key });
+            let child_path = |element: String| {
+                let mut child_path = path.clone();
+                child_path.push(element);
+                child_path
+            };
+            // The tasks of the children, in document order.
+            let mut children = Vec::new();
+            match (&node_a.data, &node_b.data) {
+                (
+                    NodeData::Sequence { items: items_a, .. },
+                    NodeData::Sequence { items: items_b, .. },
+                ) => {
+                    for (index, (item_a, item_b)) in items_a.iter().zip(items_b).enumerate() {
+                        children.push(DiffTask::Compare {
+                            a: *item_a,
+                            b: *item_b,
+                            key: Some(index.to_string()),
+                        });
+                    }
+                    for (index, item) in items_a.iter().enumerate().skip(items_b.len()) {
+                        children.push(DiffTask::Report(Difference {
+                            kind: DifferenceKind::Removed,
+                            path: child_path(index.to_string()),
+                            old_mark: self.nodes[item.index()].start_mark,
+                            new_mark: node_b.start_mark,
+                        }));
+                    }
+                    for (index, item) in items_b.iter().enumerate().skip(items_a.len()) {
+                        children.push(DiffTask::Report(Difference {
+                            kind: DifferenceKind::Added,
+                            path: child_path(index.to_string()),
+                            old_mark: node_a.start_mark,
+                            new_mark: other.nodes[item.index()].start_mark,
+                        }));
+                    }
+                }
+                (
+                    NodeData::Mapping { pairs: pairs_a, .. },
+                    NodeData::Mapping { pairs: pairs_b, .. },
+                ) => {
+                    // Each pair of `b` is matched to one pair of `a` at most.
+                    let mut used = vec![false; pairs_b.len()];
+                    for pair in pairs_a {
+                        let matching =
+                            pairs_b
+                                .iter()
+                                .enumerate()
+                                .position(|(position, candidate)| {
+                                    !used[position]
+                                        && self.nodes_equal(
+                                            pair.key,
+                                            other,
+                                            candidate.key,
+                                            &mut comparison,
+                                        )
+                                });
+                        if let Some(position) = matching {
+                            used[position] = true;
+                            children.push(DiffTask::Compare {
+                                a: pair.value,
+                                b: pairs_b[position].value,
+                                key: Some(self.key_text(pair.key)),
+                            });
+                        } else {
+                            children.push(DiffTask::Report(Difference {
+                                kind: DifferenceKind::Removed,
+                                path: child_path(self.key_text(pair.key)),
+                                old_mark: self.nodes[pair.key.index()].start_mark,
+                                new_mark: node_b.start_mark,
+                            }));
+                        }
+                    }
+                    for (candidate, _) in pairs_b.iter().zip(used).filter(|(_, used)| !used) {
+                        children.push(DiffTask::Report(Difference {
+                            kind: DifferenceKind::Added,
+                            path: child_path(other.key_text(candidate.key)),
+                            old_mark: node_a.start_mark,
+                            new_mark: other.nodes[candidate.key.index()].start_mark,
+                        }));
+                    }
+                }
+                _ => children.push(DiffTask::Report(changed(&path))),
+            }
+            tasks.extend(children.into_iter().rev());
+        }
+    }
+
+    /// The path element of the mapping key `key`: the value of a scalar key,
+    /// or a flow-style rendering of a collection key.
+    pub(crate) fn key_text(&self, key: NodeId) -> String {
+        enum Part {
+            Node(NodeId),
+            Text(&'static str),
+            Leave(NodeId),
+        }
+
+        let mut text = String::new();
+        let mut visiting = HashSet::new();
+        // The parts left to write, last first.
+        let mut parts = vec![Part::Node(key)];
+        while let Some(part) = parts.pop() {
+            let index = match part {
+                Part::Node(index) => index,
+                Part::Text(part) => {
+                    text.push_str(part);
+                    continue;
+                }
+                Part::Leave(index) => {
+                    visiting.remove(&index);
+                    continue;
+                }
+            };
+            if !visiting.insert(index) {
+                text.push('*');
+                continue;
+            }
+            parts.push(Part::Leave(index));
+            match &self.nodes[index.index()].data {
+                NodeData::Scalar { value, .. } => text.push_str(value),
+                NodeData::Sequence { items, .. } => {
+                    text.push('[');
+                    parts.push(Part::Text("]"));
+                    for (position, item) in items.iter().enumerate().rev() {
+                        parts.push(Part::Node(*item));
+                        if position > 0 {
+                            parts.push(Part::Text(", "));
+                        }
+                    }
+                }
+                NodeData::Mapping { pairs, .. } => {
+                    text.push('{');
+                    parts.push(Part::Text("}"));
+                    for (position, pair) in pairs.iter().enumerate().rev() {
+                        parts.push(Part::Node(pair.value));
+                        parts.push(Part::Text(": "));
+                        parts.push(Part::Node(pair.key));
+                        if position > 0 {
+                            parts.push(Part::Text(", "));
+                        }
+                    }
+                }
+                NodeData::NoNode => {}
+            }
+        }
+        text
+    }
+
     /// Parse the input stream and produce the next YAML document.
     ///
     /// Call this function subsequently to produce a sequence of documents
@@ -296,6 +1189,7 @@ impl Document {
             version_directive,
             tag_directives,
             implicit,
+            ..
         } = event.data
         {
             self.version_directive = version_directive;
@@ -314,7 +1208,7 @@ impl Document {
         }
     }
 
-    fn load_nodes(&mut self, parser: &mut Parser, ctx: &mut Vec<i32>) -> Result<()> {
+    fn load_nodes(&mut self, parser: &mut Parser, ctx: &mut Vec<Parent>) -> Result<()> {
         let end_implicit;
         let end_mark;
 
@@ -324,7 +1218,7 @@ impl Document {
                 EventData::StreamStart { .. } => panic!("unexpected stream start event"),
                 EventData::StreamEnd => panic!("unexpected stream end event"),
                 EventData::DocumentStart { .. } => panic!("unexpected document start event"),
-                EventData::DocumentEnd { implicit } => {
+                EventData::DocumentEnd { implicit, .. } => {
                     end_implicit = implicit;
                     end_mark = event.end_mark;
                     break;
@@ -338,14 +1232,14 @@ impl Document {
                 EventData::SequenceStart { .. } => {
                     self.load_sequence(parser, event, ctx)?;
                 }
-                EventData::SequenceEnd => {
-                    self.load_sequence_end(event, ctx)?;
+                EventData::SequenceEnd { .. } => {
+                    self.load_sequence_end(parser, event, ctx)?;
                 }
                 EventData::MappingStart { .. } => {
                     self.load_mapping(parser, event, ctx)?;
                 }
-                EventData::MappingEnd => {
-                    self.load_mapping_end(event, ctx)?;
+                EventData::MappingEnd { .. } => {
+                    self.load_mapping_end(parser, event, ctx)?;
                 }
             }
         }
@@ -354,54 +1248,106 @@ impl Document {
         Ok(())
     }
 
+    /// Count a new node towards the alias expansion limit.
+    fn expand_node(&mut self, parser: &mut Parser, id: NodeId, collection: bool) -> Result<()> {
+        let Some(max) = parser.scanner.limits.max_alias_expansion else {
+            return Ok(());
+        };
+        let expansion = &mut parser.expansion;
+        expansion
+            .sizes
+            .push(if collection { expansion.total } else { 1 });
+        expansion.total += 1;
+        if expansion.total > max {
+            return Err(Error::composer(
+                "",
+                Mark::default(),
+                "exceeded the alias expansion limit",
+                self.nodes[id.index()].start_mark,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Count an alias of the node `id` towards the alias expansion limit.
+    fn expand_alias(parser: &mut Parser, id: NodeId, ctx: &[Parent], mark: Mark) -> Result<()> {
+        let Some(max) = parser.scanner.limits.max_alias_expansion else {
+            return Ok(());
+        };
+        if ctx.iter().any(|parent| parent.id == id) {
+            return Err(Error::composer(
+                "",
+                Mark::default(),
+                "found recursive alias, which exceeds the alias expansion limit",
+                mark,
+            ));
+        }
+        let expansion = &mut parser.expansion;
+        expansion.total = expansion.total.saturating_add(expansion.sizes[id.index()]);
+        if expansion.total > max {
+            return Err(Error::composer(
+                "",
+                Mark::default(),
+                "exceeded the alias expansion limit",
+                mark,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Record the expanded size of a finished collection.
+    fn finish_expansion(parser: &mut Parser, id: NodeId) {
+        if parser.scanner.limits.max_alias_expansion.is_some() {
+            let expansion = &mut parser.expansion;
+            let size = &mut expansion.sizes[id.index()];
+            *size = expansion.total - *size;
+        }
+    }
+
     fn register_anchor(
         &mut self,
         parser: &mut Parser,
-        index: i32,
+        id: NodeId,
         anchor: Option<String>,
     ) -> Result<()> {
         let Some(anchor) = anchor else {
             return Ok(());
         };
+        self.nodes[id.index()].anchor = Some(anchor.clone());
         let data = AliasData {
             anchor,
-            index,
-            mark: self.nodes[index as usize - 1].start_mark,
+            index: id,
+            mark: self.nodes[id.index()].start_mark,
         };
-        for alias_data in &parser.aliases {
-            if alias_data.anchor == data.anchor {
-                return Err(Error::composer(
-                    "found duplicate anchor; first occurrence",
-                    alias_data.mark,
-                    "second occurrence",
-                    data.mark,
-                ));
+        if !parser.allow_anchor_redefinition {
+            for alias_data in &parser.aliases {
+                if alias_data.anchor == data.anchor {
+                    return Err(Error::composer(
+                        "found duplicate anchor; first occurrence",
+                        alias_data.mark,
+                        "second occurrence",
+                        data.mark,
+                    ));
+                }
             }
         }
         parser.aliases.push(data);
         Ok(())
     }
 
-    fn load_node_add(&mut self, ctx: &[i32], index: i32) -> Result<()> {
-        let Some(parent_index) = ctx.last() else {
+    fn load_node_add(&mut self, ctx: &mut [Parent], id: NodeId) -> Result<()> {
+        let Some(parent) = ctx.last_mut() else {
             return Ok(());
         };
-        let parent_index = *parent_index;
-        let parent = &mut self.nodes[parent_index as usize - 1];
-        match parent.data {
+        match self.nodes[parent.id.index()].data {
             NodeData::Sequence { ref mut items, .. } => {
-                items.push(index);
-            }
-            NodeData::Mapping { ref mut pairs, .. } => match pairs.last_mut() {
-                // If the last pair does not have a value, set `index` as the value.
-                Some(pair @ NodePair { value: 0, .. }) => {
-                    pair.value = index;
-                }
-                // Otherwise push a new pair where `index` is the key.
-                _ => pairs.push(NodePair {
-                    key: index,
-                    value: 0,
-                }),
+                items.push(id);
+            }
+            NodeData::Mapping { ref mut pairs, .. } => match parent.key.take() {
+                // If there is a key without a value, `id` is the value.
+                Some(key) => pairs.push(NodePair { key, value: id }),
+                // Otherwise `id` is the key of the next pair.
+                None => parent.key = Some(id),
             },
             _ => {
                 panic!("document parent node is not a sequence or a mapping")
@@ -410,14 +1356,17 @@ impl Document {
         Ok(())
     }
 
-    fn load_alias(&mut self, parser: &mut Parser, event: Event, ctx: &[i32]) -> Result<()> {
+    fn load_alias(&mut self, parser: &mut Parser, event: Event, ctx: &mut [Parent]) -> Result<()> {
         let EventData::Alias { anchor } = &event.data else {
             unreachable!()
         };
 
-        for alias_data in &parser.aliases {
+        // The most recent definition of a redefined anchor takes precedence.
+        for alias_data in parser.aliases.iter().rev() {
             if alias_data.anchor == *anchor {
-                return self.load_node_add(ctx, alias_data.index);
+                let id = alias_data.index;
+                Self::expand_alias(parser, id, ctx, event.start_mark)?;
+                return self.load_node_add(ctx, id);
             }
         }
 
@@ -429,7 +1378,7 @@ impl Document {
         ))
     }
 
-    fn load_scalar(&mut self, parser: &mut Parser, event: Event, ctx: &[i32]) -> Result<()> {
+    fn load_scalar(&mut self, parser: &mut Parser, event: Event, ctx: &mut [Parent]) -> Result<()> {
         let EventData::Scalar {
             mut tag,
             value,
@@ -441,26 +1390,42 @@ impl Document {
             unreachable!()
         };
 
-        if tag.is_none() || tag.as_deref() == Some("!") {
+        if tag.is_none() && style == ScalarStyle::Plain {
+            let Some(resolved) = parser.schema.resolve(&value) else {
+                return Err(Error::composer(
+                    "",
+                    Mark::default(),
+                    "found a plain scalar that does not match the JSON schema",
+                    event.start_mark,
+                ));
+            };
+            // Only an untagged plain `<<` is a merge key, so remember it here,
+            // where it is still known whether the tag was explicit.
+            let resolved = if value == "<<" { MERGE_TAG } else { resolved };
+            tag = Some(String::from(resolved));
+        } else if tag.is_none() || tag.as_deref() == Some("!") {
             tag = Some(String::from(DEFAULT_SCALAR_TAG));
         }
         let node = Node {
             data: NodeData::Scalar { value, style },
             tag,
+            schema: parser.schema,
+            anchor: None,
             start_mark: event.start_mark,
             end_mark: event.end_mark,
         };
+        let id = NodeId::from_index(self.nodes.len());
         self.nodes.push(node);
-        let index: i32 = self.nodes.len() as i32;
-        self.register_anchor(parser, index, anchor)?;
-        self.load_node_add(ctx, index)
+        self.expand_node(parser, id, false)?;
+        self.register_anchor(parser, id, anchor)?;
+        self.load_node_add(ctx, id)
     }
 
     fn load_sequence(
         &mut self,
         parser: &mut Parser,
         event: Event,
-        ctx: &mut Vec<i32>,
+        ctx: &mut Vec<Parent>,
     ) -> Result<()> {
         let EventData::SequenceStart {
             anchor,
@@ -484,27 +1449,36 @@ impl Document {
                 style,
             },
             tag,
+            schema: parser.schema,
+            anchor: None,
             start_mark: event.start_mark,
             end_mark: event.end_mark,
         };
 
+        let id = NodeId::from_index(self.nodes.len());
         self.nodes.push(node);
-        let index: i32 = self.nodes.len() as i32;
-        self.register_anchor(parser, index, anchor)?;
-        self.load_node_add(ctx, index)?;
-        ctx.push(index);
+        self.expand_node(parser, id, true)?;
+        self.register_anchor(parser, id, anchor)?;
+        self.load_node_add(ctx, id)?;
+        ctx.push(Parent { id, key: None });
         Ok(())
     }
 
-    fn load_sequence_end(&mut self, event: Event, ctx: &mut Vec<i32>) -> Result<()> {
-        let Some(index) = ctx.last().copied() else {
+    fn load_sequence_end(
+        &mut self,
+        parser: &mut Parser,
+        event: Event,
+        ctx: &mut Vec<Parent>,
+    ) -> Result<()> {
+        let Some(index) = ctx.last().map(|parent| parent.id) else {
             panic!("sequence_end without a current sequence")
         };
         assert!(matches!(
-            self.nodes[index as usize - 1].data,
+            self.nodes[index.index()].data,
             NodeData::Sequence { .. }
         ));
-        self.nodes[index as usize - 1].end_mark = event.end_mark;
+        self.nodes[index.index()].end_mark = event.end_mark;
+        Self::finish_expansion(parser, index);
         ctx.pop();
         Ok(())
     }
@@ -513,7 +1487,7 @@ impl Document {
         &mut self,
         parser: &mut Parser,
         event: Event,
-        ctx: &mut Vec<i32>,
+        ctx: &mut Vec<Parent>,
     ) -> Result<()> {
         let EventData::MappingStart {
             anchor,
@@ -536,40 +1510,382 @@ impl Document {
                 style,
             },
             tag,
+            schema: parser.schema,
+            anchor: None,
             start_mark: event.start_mark,
             end_mark: event.end_mark,
         };
+        let id = NodeId::from_index(self.nodes.len());
         self.nodes.push(node);
-        let index: i32 = self.nodes.len() as i32;
-        self.register_anchor(parser, index, anchor)?;
-        self.load_node_add(ctx, index)?;
-        ctx.push(index);
+        self.expand_node(parser, id, true)?;
+        self.register_anchor(parser, id, anchor)?;
+        self.load_node_add(ctx, id)?;
+        ctx.push(Parent { id, key: None });
         Ok(())
     }
 
-    fn load_mapping_end(&mut self, event: Event, ctx: &mut Vec<i32>) -> Result<()> {
-        let Some(index) = ctx.last().copied() else {
+    fn load_mapping_end(
+        &mut self,
+        parser: &mut Parser,
+        event: Event,
+        ctx: &mut Vec<Parent>,
+    ) -> Result<()> {
+        let Some(index) = ctx.last().map(|parent| parent.id) else {
             panic!("mapping_end without a current mapping")
         };
         assert!(matches!(
-            self.nodes[index as usize - 1].data,
+            self.nodes[index.index()].data,
             NodeData::Mapping { .. }
         ));
-        self.nodes[index as usize - 1].end_mark = event.end_mark;
+        self.nodes[index.index()].end_mark = event.end_mark;
+        if parser.strict {
+            self.check_duplicate_keys(index)?;
+        }
+        Self::finish_expansion(parser, index);
         ctx.pop();
         Ok(())
     }
 
+    /// Apply the merge keys (`<<`) of the YAML 1.1 merge type.
+    ///
+    /// The value of a merge key is a mapping, or a sequence of mappings, whose
+    /// pairs are inserted into the mapping that contains the merge key. The
+    /// keys of the mapping itself take precedence over merged keys, and a
+    /// mapping earlier in a sequence over a later one. A merge key is a scalar
+    /// with the tag `!!merge`, which [`Document::load()`] gives to an untagged
+    /// plain `<<`. A `<<` with an explicit tag, such as `!!str <<`, or a quoted
+    /// `'<<'` is an ordinary key.
+    ///
+    /// The merged pairs come before the pairs of the mapping itself, and refer
+    /// to the same nodes as the merged mapping.
+    pub fn apply_merge_keys(&mut self) -> Result<()> {
+        let mut merged = vec![MergeState::Pending; self.nodes.len()];
+        for index in 0..self.nodes.len() {
+            self.merge_mapping(NodeId::from_index(index), &mut merged)?;
+        }
+        Ok(())
+    }
+
+    fn merge_mapping(&mut self, index: NodeId, merged: &mut [MergeState]) -> Result<()> {
+        match merged[index.index()] {
+            MergeState::Pending => {}
+            MergeState::Merging => {
+                return Err(Error::composer(
+                    "",
+                    Mark::default(),
+                    "found recursive merge",
+                    self.nodes[index.index()].start_mark,
+                ))
+            }
+            MergeState::Merged => return Ok(()),
+        }
+        let NodeData::Mapping { pairs, .. } = &self.nodes[index.index()].data else {
+            merged[index.index()] = MergeState::Merged;
+            return Ok(());
+        };
+        let (merge_pairs, own_pairs): (Vec<NodePair>, Vec<NodePair>) =
+            pairs.iter().partition(|pair| self.is_merge_key(pair.key));
+        if merge_pairs.is_empty() {
+            merged[index.index()] = MergeState::Merged;
+            return Ok(());
+        }
+        merged[index.index()] = MergeState::Merging;
+
+        let mut sources = Vec::new();
+        for pair in &merge_pairs {
+            let value = &self.nodes[pair.value.index()];
+            match &value.data {
+                NodeData::Mapping { .. } => sources.push(pair.value),
+                NodeData::Sequence { items, .. } => {
+                    for item in items {
+                        let item_node = &self.nodes[item.index()];
+                        if !matches!(item_node.data, NodeData::Mapping { .. }) {
+                            return Err(Error::composer(
+                                "while applying a merge key",
+                                self.nodes[pair.key.index()].start_mark,
+                                "expected a mapping for merging",
+                                item_node.start_mark,
+                            ));
+                        }
+                        sources.push(*item);
+                    }
+                }
+                _ => {
+                    return Err(Error::composer(
+                        "while applying a merge key",
+                        self.nodes[pair.key.index()].start_mark,
+                        "expected a mapping or a sequence of mappings for merging",
+                        value.start_mark,
+                    ))
+                }
+            }
+        }
+
+        let mut new_pairs: Vec<NodePair> = Vec::new();
+        let mut comparison = Comparison::default();
+        for source in sources {
+            self.merge_mapping(source, merged)?;
+            let NodeData::Mapping { pairs, .. } = &self.nodes[source.index()].data else {
+                unreachable!()
+            };
+            for pair in pairs {
+                let overridden = own_pairs
+                    .iter()
+                    .chain(&new_pairs)
+                    .any(|other| self.keys_equal(other.key, pair.key, &mut comparison));
+                if !overridden {
+                    new_pairs.push(*pair);
+                }
+            }
+        }
+        new_pairs.extend(own_pairs);
+        if let NodeData::Mapping { pairs, .. } = &mut self.nodes[index.index()].data {
+            *pairs = new_pairs;
+        }
+        merged[index.index()] = MergeState::Merged;
+        Ok(())
+    }
+
+    fn is_merge_key(&self, index: NodeId) -> bool {
+        let node = &self.nodes[index.index()];
+        match &node.data {
+            NodeData::Scalar { .. } => node.tag.as_deref() == Some(MERGE_TAG),
+            _ => false,
+        }
+    }
+
+    /// Fail if the mapping `index` has two equal keys.
+    fn check_duplicate_keys(&self, index: NodeId) -> Result<()> {
+        let NodeData::Mapping { pairs, .. } = &self.nodes[index.index()].data else {
+            unreachable!()
+        };
+        let hashes = self.structure_hashes(pairs.iter().map(|pair| pair.key));
+        let mut keys: HashMap<u64, Vec<NodeId>> = HashMap::new();
+        let mut comparison = Comparison::default();
+        for pair in pairs {
+            let candidates = keys.entry(hashes[&pair.key].0).or_default();
+            if let Some(first) = candidates
+                .iter()
+                .find(|first| self.keys_equal(**first, pair.key, &mut comparison))
+            {
+                return Err(Error::composer(
+                    "found duplicate key; first occurrence",
+                    self.nodes[first.index()].start_mark,
+                    "second occurrence",
+                    self.nodes[pair.key.index()].start_mark,
+                ));
+            }
+            candidates.push(pair.key);
+        }
+        Ok(())
+    }
+
+    /// Are the keys `a` and `b` equal?
+    ///
+    /// Nodes are equal if they have the same tag and equal contents: scalars
+    /// the same value, sequences equal items in the same order, and mappings
+    /// equal pairs in any order.
+    fn keys_equal(&self, a: NodeId, b: NodeId, comparison: &mut Comparison) -> bool {
+        self.nodes_equal(a, self, b, comparison)
+    }
+
+    /// Compare the node `a` of this document to the node `b` of `other`.
+    fn nodes_equal(
+        &self,
+        a: NodeId,
+        other: &Document,
+        b: NodeId,
+        comparison: &mut Comparison,
+    ) -> bool {
+        self.compare_nodes(a, other, b, comparison).0
+    }
+
+    /// Compare the node `a` of this document to the node `b` of `other`.
+    ///
+    /// If the nodes are equal only as long as some pairs in
+    /// `comparison.assumed` are, also returns the lowest position of these
+    /// pairs, or else `usize::MAX`. Only the other results are known for good.
+    ///
+    /// Collections are compared with an explicit stack, so that deeply nested
+    /// nodes cannot overflow the call stack.
+    fn compare_nodes(
+        &self,
+        a: NodeId,
+        other: &Document,
+        b: NodeId,
+        comparison: &mut Comparison,
+    ) -> (bool, usize) {
+        let mut frames = Vec::new();
+        let mut result = self.start_comparison(a, other, b, comparison, &mut frames);
+        while let Some(frame) = frames.last_mut() {
+            let child = result.map(|(equal, assumed)| {
+                frame.lowest = frame.lowest.min(assumed);
+                equal
+            });
+            match frame.step(self, other, child) {
+                ComparisonStep::Compare(a, b) => {
+                    result = self.start_comparison(a, other, b, comparison, &mut frames);
+                }
+                ComparisonStep::Done(equal) => {
+                    let Some(frame) = frames.pop() else {
+                        unreachable!()
+                    };
+                    comparison.assumed.remove(&(frame.a, frame.b));
+                    // Nodes that differ differ whatever is assumed, and nodes
+                    // that are equal as long as they are themselves are equal.
+                    let mut lowest = frame.lowest;
+                    if !equal || lowest >= frames.len() {
+                        comparison.known.insert((frame.a, frame.b), equal);
+                        lowest = usize::MAX;
+                    }
+                    result = Some((equal, lowest));
+                }
+            }
+        }
+        let Some(result) = result else { unreachable!() };
+        result
+    }
+
+    /// Start comparing the node `a` of this document to the node `b` of
+    /// `other`.
+    ///
+    /// Returns the result if it does not depend on the children of the nodes,
+    /// or else pushes the two collections onto `frames` and returns `None`.
+    fn start_comparison(
+        &self,
+        a: NodeId,
+        other: &Document,
+        b: NodeId,
+        comparison: &mut Comparison,
+        frames: &mut Vec<ComparisonFrame>,
+    ) -> Option<(bool, usize)> {
+        if core::ptr::eq(self, other) && a == b {
+            return Some((true, usize::MAX));
+        }
+        if let Some(equal) = comparison.known.get(&(a, b)) {
+            return Some((*equal, usize::MAX));
+        }
+        if let Some(position) = comparison.assumed.get(&(a, b)) {
+            return Some((true, *position));
+        }
+        let (node_a, node_b) = (&self.nodes[a.index()], &other.nodes[b.index()]);
+        let equal = node_a.tag == node_b.tag
+            && match (&node_a.data, &node_b.data) {
+                (
+                    NodeData::Scalar { value: value_a, .. },
+                    NodeData::Scalar { value: value_b, .. },
+                ) => value_a == value_b,
+                (
+                    NodeData::Sequence { items: items_a, .. },
+                    NodeData::Sequence { items: items_b, .. },
+                ) if items_a.len() == items_b.len() => {
+                    comparison.assumed.insert((a, b), frames.len());
+                    frames.push(ComparisonFrame::new(a, b, 0));
+                    return None;
+                }
+                (
+                    NodeData::Mapping { pairs: pairs_a, .. },
+                    NodeData::Mapping { pairs: pairs_b, .. },
+                ) if pairs_a.len() == pairs_b.len() => {
+                    comparison.assumed.insert((a, b), frames.len());
+                    frames.push(ComparisonFrame::new(a, b, pairs_b.len()));
+                    return None;
+                }
+                _ => false,
+            };
+        comparison.known.insert((a, b), equal);
+        Some((equal, usize::MAX))
+    }
+
+    /// Hash the nodes reachable from `roots` by their whole structure, so that
+    /// equal nodes have the same hash.
+    ///
+    /// The structure of a node that leads to a recursive alias has no end, so
+    /// such a node only adds its tag, kind and size to the hashes of the nodes
+    /// that contain it. Besides the hash, the map tells if a node is such a
+    /// node.
+    fn structure_hashes(
+        &self,
+        roots: impl IntoIterator<Item = NodeId>,
+    ) -> HashMap<NodeId, (u64, bool)> {
+        let mut hashes: HashMap<NodeId, (u64, bool)> = HashMap::new();
+        let mut visiting = HashSet::new();
+        // The nodes being hashed, the position of their next child, and if
+        // their structure ends.
+        let mut stack: Vec<(NodeId, usize, bool)> = Vec::new();
+        for root in roots {
+            if hashes.contains_key(&root) {
+                continue;
+            }
+            visiting.insert(root);
+            stack.push((root, 0, true));
+            while let Some((id, next, finite)) = stack.last_mut() {
+                if let Some(child) = self.nodes[id.index()].child(*next) {
+                    *next += 1;
+                    if let Some((_, child_finite)) = hashes.get(&child) {
+                        *finite &= *child_finite;
+                    } else if visiting.contains(&child) {
+                        *finite = false;
+                    } else {
+                        visiting.insert(child);
+                        stack.push((child, 0, true));
+                    }
+                    continue;
+                }
+                let (id, finite) = (*id, *finite);
+                stack.pop();
+                visiting.remove(&id);
+                let hash = self.structure_hash(id, &hashes);
+                hashes.insert(id, (hash, finite));
+                if let Some((_, _, parent_finite)) = stack.last_mut() {
+                    *parent_finite &= finite;
+                }
+            }
+        }
+        hashes
+    }
+
+    /// The hash of the node `index`, given the hashes of its children.
+    fn structure_hash(&self, index: NodeId, known: &HashMap<NodeId, (u64, bool)>) -> u64 {
+        let node = &self.nodes[index.index()];
+        let mut hasher = DefaultHasher::new();
+        node.hash_kind(&mut hasher);
+        let child_hash = |child: NodeId| {
+            if let Some((hash, true)) = known.get(&child) {
+                return *hash;
+            }
+            let mut child_hasher = DefaultHasher::new();
+            self.nodes[child.index()].hash_kind(&mut child_hasher);
+            child_hasher.finish()
+        };
+        match &node.data {
+            NodeData::Sequence { items, .. } => {
+                for item in items {
+                    child_hash(*item).hash(&mut hasher);
+                }
+            }
+            NodeData::Mapping { pairs, .. } => {
+                // The order of the pairs does not matter.
+                let pairs_hash = pairs.iter().fold(0_u64, |sum, pair| {
+                    let mut pair_hasher = DefaultHasher::new();
+                    child_hash(pair.key).hash(&mut pair_hasher);
+                    child_hash(pair.value).hash(&mut pair_hasher);
+                    sum.wrapping_add(pair_hasher.finish())
+                });
+                pairs_hash.hash(&mut hasher);
+            }
+            _ => {}
+        }
+        hasher.finish()
+    }
+
     /// Emit a YAML document.
     ///
     /// The document object may be generated using the [`Document::load()`]
     /// function or the [`Document::new()`] function.
-    pub fn dump(mut self, emitter: &mut Emitter) -> Result<()> {
+    pub fn dump(&self, emitter: &mut Emitter) -> Result<()> {
         if !emitter.opened {
-            if let Err(err) = emitter.open() {
-                emitter.reset_anchors();
-                return Err(err);
-            }
+            emitter.open()?;
         }
         if self.nodes.is_empty() {
             // TODO: Do we really want to close the emitter just because the
@@ -578,142 +1894,355 @@ impl Document {
             emitter.close()?;
         } else {
             assert!(emitter.opened);
-            emitter.anchors = vec![Anchors::default(); self.nodes.len()];
-            let event = Event::new(EventData::DocumentStart {
-                version_directive: self.version_directive,
-                tag_directives: core::mem::take(&mut self.tag_directives),
-                implicit: self.start_implicit,
-            });
-            emitter.emit(event)?;
-            self.anchor_node(emitter, 1);
-            self.dump_node(emitter, 1)?;
-            let event = Event::document_end(self.end_implicit);
-            emitter.emit(event)?;
+            for event in self.events_for(emitter.schema) {
+                emitter.emit(event)?;
+            }
         }
-
-        emitter.reset_anchors();
         Ok(())
     }
 
-    fn anchor_node(&self, emitter: &mut Emitter, index: i32) {
-        let node = &self.nodes[index as usize - 1];
-        emitter.anchors[index as usize - 1].references += 1;
-        if emitter.anchors[index as usize - 1].references == 1 {
+    /// Iterate over the events that make up the document.
+    ///
+    /// These are the events [`Document::dump()`] emits, from the
+    /// `DOCUMENT-START` event to the `DOCUMENT-END` event. An empty document
+    /// yields no events.
+    ///
+    /// Scalars keep their tags unless they are strings, as for an emitter with
+    /// the default schema (see [`Emitter::set_schema()`]).
+    pub fn events(&self) -> Events<'_> {
+        self.events_for(Schema::Failsafe)
+    }
+
+    /// Like [`Document::events()`], but leave out the tags of scalars that
+    /// `schema` resolves to the same tag.
+    pub(crate) fn events_for(&self, schema: Schema) -> Events<'_> {
+        let mut anchors = vec![Anchors::default(); self.nodes.len()];
+        let mut steps = Vec::new();
+        if !self.nodes.is_empty() {
+            let root = NodeId::from_index(0);
+            let mut last_anchor_id = 0;
+            self.anchor_node(&mut anchors, &mut last_anchor_id, root);
+            self.name_anchors(&mut anchors);
+            steps.push(Step::DocumentEnd);
+            steps.push(Step::Node(root));
+            steps.push(Step::DocumentStart);
+        }
+        Events {
+            document: self,
+            schema,
+            anchors,
+            steps,
+        }
+    }
+
+    fn anchor_node(&self, anchors: &mut [Anchors], last_anchor_id: &mut i32, index: NodeId) {
+        let node = &self.nodes[index.index()];
+        anchors[index.index()].references += 1;
+        if anchors[index.index()].references == 1 {
             match &node.data {
                 NodeData::Sequence { items, .. } => {
                     for item in items {
-                        emitter.anchor_node_sub(*item);
+                        self.anchor_node(anchors, last_anchor_id, *item);
                     }
                 }
                 NodeData::Mapping { pairs, .. } => {
                     for pair in pairs {
-                        emitter.anchor_node_sub(pair.key);
-                        emitter.anchor_node_sub(pair.value);
+                        self.anchor_node(anchors, last_anchor_id, pair.key);
+                        self.anchor_node(anchors, last_anchor_id, pair.value);
                     }
                 }
                 _ => {}
             }
-        } else if emitter.anchors[index as usize - 1].references == 2 {
-            emitter.last_anchor_id += 1;
-            emitter.anchors[index as usize - 1].anchor = emitter.last_anchor_id;
+        } else if anchors[index.index()].references == 2 {
+            *last_anchor_id += 1;
+            anchors[index.index()].anchor = *last_anchor_id;
         }
     }
 
-    fn dump_node(&mut self, emitter: &mut Emitter, index: i32) -> Result<()> {
-        assert!(index > 0);
-        let node = &mut self.nodes[index as usize - 1];
-        let anchor_id: i32 = emitter.anchors[index as usize - 1].anchor;
-        let mut anchor: Option<String> = None;
-        if anchor_id != 0 {
-            anchor = Some(Emitter::generate_anchor(anchor_id));
-        }
-        if emitter.anchors[index as usize - 1].serialized {
-            return Self::dump_alias(emitter, anchor.unwrap());
+    fn name_anchors(&self, anchors: &mut [Anchors]) {
+        let mut anchored: Vec<usize> = (0..self.nodes.len())
+            .filter(|&index| anchors[index].anchor != 0)
+            .collect();
+        anchored.sort_by_key(|&index| anchors[index].anchor);
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for &index in &anchored {
+            if let Some(name) = self.nodes[index].anchor.as_deref() {
+                *counts.entry(name).or_default() += 1;
+            }
         }
-        emitter.anchors[index as usize - 1].serialized = true;
 
-        let node = core::mem::take(node);
-        match node.data {
-            NodeData::Scalar { .. } => Self::dump_scalar(emitter, node, anchor),
-            NodeData::Sequence { .. } => self.dump_sequence(emitter, node, anchor),
-            NodeData::Mapping { .. } => self.dump_mapping(emitter, node, anchor),
-            _ => unreachable!("document node is neither a scalar, sequence, or a mapping"),
+        // Keep the original names that are still unique, and generate the
+        // rest without colliding with them.
+        let mut anchor_id = 0;
+        for index in anchored {
+            let name = match self.nodes[index].anchor.as_deref() {
+                Some(name) if counts[name] == 1 => String::from(name),
+                _ => loop {
+                    anchor_id += 1;
+                    let name = Emitter::generate_anchor(anchor_id);
+                    if !counts.contains_key(name.as_str()) {
+                        break name;
+                    }
+                },
+            };
+            anchors[index].name = Some(name);
         }
     }
+}
 
-    fn dump_alias(emitter: &mut Emitter, anchor: String) -> Result<()> {
-        let event = Event::new(EventData::Alias { anchor });
-        emitter.emit(event)
-    }
+/// An iterator over the events of a [`Document`].
+///
+/// See [`Document::events()`].
+#[derive(Clone)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct Events<'a> {
+    document: &'a Document,
+    schema: Schema,
+    anchors: Vec<Anchors>,
+    steps: Vec<Step>,
+}
 
-    fn dump_scalar(emitter: &mut Emitter, node: Node, anchor: Option<String>) -> Result<()> {
-        let plain_implicit = node.tag.as_deref() == Some(DEFAULT_SCALAR_TAG);
-        let quoted_implicit = node.tag.as_deref() == Some(DEFAULT_SCALAR_TAG); // TODO: Why compare twice?! (even the C code does this)
+/// The events that [`Events`] still has to produce, in reverse order.
+#[derive(Copy, Clone)]
+enum Step {
+    DocumentStart,
+    Node(NodeId),
+    SequenceEnd,
+    MappingEnd,
+    DocumentEnd,
+}
 
-        let NodeData::Scalar { value, style } = node.data else {
-            unreachable!()
+impl Iterator for Events<'_> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        let document = self.document;
+        let event = match self.steps.pop()? {
+            Step::DocumentStart => Event::new(EventData::DocumentStart {
+                version_directive: document.version_directive,
+                tag_directives: document.tag_directives.clone(),
+                implicit: document.start_implicit,
+                comments: Comments::default(),
+            }),
+            Step::Node(index) => self.node_event(index),
+            Step::SequenceEnd => Event::sequence_end(),
+            Step::MappingEnd => Event::mapping_end(),
+            Step::DocumentEnd => Event::document_end(document.end_implicit),
         };
-        let event = Event::new(EventData::Scalar {
+        Some(event)
+    }
+}
+
+impl core::iter::FusedIterator for Events<'_> {}
+
+impl Events<'_> {
+    fn node_event(&mut self, index: NodeId) -> Event {
+        let node = &self.document.nodes[index.index()];
+        let anchors = &mut self.anchors[index.index()];
+        let anchor = anchors.name.clone();
+        if anchors.serialized {
+            return Event::new(EventData::Alias {
+                anchor: anchor.unwrap(),
+            });
+        }
+        anchors.serialized = true;
+
+        match &node.data {
+            NodeData::Scalar { value, style } => self.scalar_event(node, value, *style, anchor),
+            NodeData::Sequence { items, style } => {
+                self.steps.push(Step::SequenceEnd);
+                self.steps
+                    .extend(items.iter().rev().map(|item| Step::Node(*item)));
+                Event::new(EventData::SequenceStart {
+                    anchor,
+                    tag: node.tag.clone(),
+                    implicit: node.tag.as_deref() == Some(DEFAULT_SEQUENCE_TAG),
+                    style: *style,
+                    comments: Comments::default(),
+                })
+            }
+            NodeData::Mapping { pairs, style } => {
+                self.steps.push(Step::MappingEnd);
+                for pair in pairs.iter().rev() {
+                    self.steps.push(Step::Node(pair.value));
+                    self.steps.push(Step::Node(pair.key));
+                }
+                Event::new(EventData::MappingStart {
+                    anchor,
+                    tag: node.tag.clone(),
+                    implicit: node.tag.as_deref() == Some(DEFAULT_MAPPING_TAG),
+                    style: *style,
+                    comments: Comments::default(),
+                })
+            }
+            NodeData::NoNode => {
+                unreachable!("document node is neither a scalar, sequence, or a mapping")
+            }
+        }
+    }
+
+    fn scalar_event(
+        &self,
+        node: &Node,
+        value: &str,
+        mut style: ScalarStyle,
+        anchor: Option<String>,
+    ) -> Event {
+        let quoted_implicit = node.tag.as_deref() == Some(DEFAULT_SCALAR_TAG);
+        // A plain scalar needs no tag if the schema of the reader resolves its
+        // value to the same tag.
+        let plain_implicit = node.tag.as_deref() == self.schema.resolve(value)
+            || node.tag.as_deref() == Some(MERGE_TAG) && value == "<<";
+        if plain_implicit && !quoted_implicit {
+            // Keep the value plain, or it would need its tag.
+            style = ScalarStyle::Plain;
+        }
+        Event::new(EventData::Scalar {
             anchor,
-            tag: node.tag,
-            value,
+            tag: node.tag.clone(),
+            value: String::from(value),
             plain_implicit,
             quoted_implicit,
             style,
-        });
-        emitter.emit(event)
+            comments: Comments::default(),
+        })
     }
+}
 
-    fn dump_sequence(
-        &mut self,
-        emitter: &mut Emitter,
-        node: Node,
-        anchor: Option<String>,
-    ) -> Result<()> {
-        let implicit = node.tag.as_deref() == Some(DEFAULT_SEQUENCE_TAG);
+/// The pairs of nodes compared by [`Document::nodes_equal()`].
+#[derive(Default)]
+struct Comparison {
+    /// The pairs being compared, with their positions in the stack of
+    /// comparisons, which are assumed to be equal when recursive aliases lead
+    /// back to them.
+    assumed: HashMap<(NodeId, NodeId), usize>,
+    /// The pairs known to be equal or not, so that nodes shared through
+    /// aliases are only compared once.
+    known: HashMap<(NodeId, NodeId), bool>,
+}
 
-        let NodeData::Sequence { items, style } = node.data else {
-            unreachable!()
-        };
-        let event = Event::new(EventData::SequenceStart {
-            anchor,
-            tag: node.tag,
-            implicit,
-            style,
-        });
+/// The work left to [`Document::diff_node()`].
+enum DiffTask {
+    /// Compare two nodes, at the path element `key` below the current path.
+    Compare {
+        a: NodeId,
+        b: NodeId,
+        key: Option<String>,
+    },
+    /// Add a difference.
+    Report(Difference),
+    /// Finish comparing two nodes, and leave their path element if `key`.
+    Leave { a: NodeId, b: NodeId, key: bool },
+}
 
-        emitter.emit(event)?;
-        for item in items {
-            self.dump_node(emitter, item)?;
+/// Two collections being compared by [`Document::compare_nodes()`].
+struct ComparisonFrame {
+    a: NodeId,
+    b: NodeId,
+    /// The lowest position of the assumed pairs that the comparison of the
+    /// children depends on.
+    lowest: usize,
+    /// The item, or the pair of `a`, being compared.
+    index: usize,
+    /// The pair of `b` being compared to the pair `index` of `a`.
+    candidate: usize,
+    /// If the values of the pairs are being compared, after their keys.
+    value: bool,
+    /// The pairs of `b` matched to pairs of `a`, which no other pair of `a`
+    /// may match.
+    used: Vec<bool>,
+}
+
+/// What [`ComparisonFrame::step()`] needs next.
+enum ComparisonStep {
+    /// The result of comparing two children.
+    Compare(NodeId, NodeId),
+    /// Nothing: the collections are equal or not.
+    Done(bool),
+}
+
+impl ComparisonFrame {
+    fn new(a: NodeId, b: NodeId, pairs: usize) -> Self {
+        ComparisonFrame {
+            a,
+            b,
+            lowest: usize::MAX,
+            index: 0,
+            candidate: 0,
+            value: false,
+            used: vec![false; pairs],
         }
-        let event = Event::sequence_end();
-        emitter.emit(event)
     }
 
-    fn dump_mapping(
+    /// Take the result of comparing the last children, if any, and move on to
+    /// the next ones.
+    fn step(
         &mut self,
-        emitter: &mut Emitter,
-        node: Node,
-        anchor: Option<String>,
-    ) -> Result<()> {
-        let implicit = node.tag.as_deref() == Some(DEFAULT_MAPPING_TAG);
-
-        let NodeData::Mapping { pairs, style } = node.data else {
-            unreachable!()
-        };
-        let event = Event::new(EventData::MappingStart {
-            anchor,
-            tag: node.tag,
-            implicit,
-            style,
-        });
-
-        emitter.emit(event)?;
-        for pair in pairs {
-            self.dump_node(emitter, pair.key)?;
-            self.dump_node(emitter, pair.value)?;
+        document: &Document,
+        other: &Document,
+        child: Option<bool>,
+    ) -> ComparisonStep {
+        match (
+            &document.nodes[self.a.index()].data,
+            &other.nodes[self.b.index()].data,
+        ) {
+            (
+                NodeData::Sequence { items: items_a, .. },
+                NodeData::Sequence { items: items_b, .. },
+            ) => {
+                match child {
+                    Some(false) => return ComparisonStep::Done(false),
+                    Some(true) => self.index += 1,
+                    None => {}
+                }
+                match items_a.get(self.index) {
+                    Some(item) => ComparisonStep::Compare(*item, items_b[self.index]),
+                    None => ComparisonStep::Done(true),
+                }
+            }
+            (
+                NodeData::Mapping { pairs: pairs_a, .. },
+                NodeData::Mapping { pairs: pairs_b, .. },
+            ) => {
+                match child {
+                    Some(true) if !self.value => self.value = true,
+                    Some(true) => {
+                        self.used[self.candidate] = true;
+                        self.index += 1;
+                        self.candidate = 0;
+                        self.value = false;
+                    }
+                    Some(false) => {
+                        self.candidate += 1;
+                        self.value = false;
+                    }
+                    None => {}
+                }
+                let Some(pair) = pairs_a.get(self.index) else {
+                    return ComparisonStep::Done(true);
+                };
+                while self.used.get(self.candidate) == Some(&true) {
+                    self.candidate += 1;
+                }
+                let Some(candidate) = pairs_b.get(self.candidate) else {
+                    return ComparisonStep::Done(false);
+                };
+                if self.value {
+                    ComparisonStep::Compare(pair.value, candidate.value)
+                } else {
+                    ComparisonStep::Compare(pair.key, candidate.key)
+                }
+            }
+            _ => unreachable!(),
         }
-        let event = Event::mapping_end();
-        emitter.emit(event)
     }
 }
+
+/// The progress of [`Document::apply_merge_keys()`] on a node.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum MergeState {
+    Pending,
+    Merging,
+    Merged,
+}
@@ -6,8 +6,8 @@ use crate::macros::{
     is_alpha, is_ascii, is_blank, is_blankz, is_bom, is_break, is_breakz, is_printable, is_space,
 };
 use crate::{
-    Break, Encoding, Error, Event, EventData, MappingStyle, Result, ScalarStyle, SequenceStyle,
-    TagDirective, VersionDirective, OUTPUT_BUFFER_SIZE,
+    Break, Comments, Encoding, Error, Event, EventData, MappingStyle, Result, ScalarStyle, Schema,
+    SequenceStyle, TagDirective, VersionDirective, OUTPUT_BUFFER_SIZE,
 };
 
 /// The emitter structure.
@@ -37,6 +37,8 @@ pub struct Emitter<'w> {
     pub(crate) best_width: i32,
     /// Allow unescaped non-ASCII characters?
     pub(crate) unicode: bool,
+    /// The schema that readers of the output resolve plain scalars with.
+    pub(crate) schema: Schema,
     /// The preferred line break.
     pub(crate) line_break: Break,
     /// The stack of states.
@@ -75,11 +77,11 @@ pub struct Emitter<'w> {
     pub(crate) opened: bool,
     /// If the stream was already closed?
     pub(crate) closed: bool,
-    /// The information associated with the document nodes.
-    // Note: Same length as `document.nodes`.
-    pub(crate) anchors: Vec<Anchors>,
-    /// The last assigned anchor id.
-    pub(crate) last_anchor_id: i32,
+    /// The trailing comments of the open documents and collections.
+    pub(crate) trailing_comments: Vec<Vec<String>>,
+    /// The inline comments of the last entry of the open flow collection,
+    /// which are written after the ',' that ends the entry.
+    pub(crate) flow_inline_comments: Vec<String>,
 }
 
 impl<'a> Default for Emitter<'a> {
@@ -131,12 +133,14 @@ pub enum EmitterState {
     End = 17,
 }
 
-#[derive(Copy, Clone, Default)]
+#[derive(Clone, Default)]
 pub(crate) struct Anchors {
     /// The number of references.
     pub references: i32,
     /// The anchor id.
     pub anchor: i32,
+    /// The anchor name.
+    pub name: Option<String>,
     /// If the node has been emitted?
     pub serialized: bool,
 }
@@ -187,6 +191,7 @@ impl<'w> Emitter<'w> {
             best_indent: 0,
             best_width: 0,
             unicode: false,
+            schema: Schema::default(),
             line_break: Break::default(),
             states: Vec::with_capacity(16),
             state: EmitterState::default(),
@@ -206,8 +211,8 @@ impl<'w> Emitter<'w> {
             open_ended: 0,
             opened: false,
             closed: false,
-            anchors: Vec::new(),
-            last_anchor_id: 0,
+            trailing_comments: Vec::new(),
+            flow_inline_comments: Vec::new(),
         }
     }
 
@@ -251,7 +256,7 @@ impl<'w> Emitter<'w> {
         if self.encoding == Encoding::Any {
             self.set_encoding(Encoding::Utf8);
         } else if self.encoding != Encoding::Utf8 {
-            panic!("cannot output UTF-16 to String")
+            panic!("cannot output UTF-16 or UTF-32 to String")
         }
         output.clear();
         self.write_handler = Some(output);
@@ -290,6 +295,17 @@ impl<'w> Emitter<'w> {
         self.unicode = unicode;
     }
 
+    /// Set the schema that readers of the output resolve untagged plain
+    /// scalars with.
+    ///
+    /// [`Document::dump()`](crate::Document::dump) leaves out the tags of
+    /// scalars that this schema resolves to the same tag. The default is
+    /// [`Schema::Failsafe`], so every scalar that is not a string keeps its
+    /// tag.
+    pub fn set_schema(&mut self, schema: Schema) {
+        self.schema = schema;
+    }
+
     /// Set the preferred line break.
     pub fn set_break(&mut self, line_break: Break) {
         self.line_break = line_break;
@@ -418,8 +434,8 @@ impl<'w> Emitter<'w> {
 
                 EventData::StreamEnd
                 | EventData::DocumentEnd { .. }
-                | EventData::SequenceEnd
-                | EventData::MappingEnd => {
+                | EventData::SequenceEnd { .. }
+                | EventData::MappingEnd { .. } => {
                     level -= 1;
                 }
                 _ => {}
@@ -532,6 +548,7 @@ impl<'w> Emitter<'w> {
             version_directive,
             tag_directives,
             implicit,
+            comments,
         } = &event.data
         {
             let default_tag_directives: [TagDirective; 2] = [
@@ -564,6 +581,8 @@ impl<'w> Emitter<'w> {
                 self.write_indent()?;
             }
             self.open_ended = 0;
+            self.write_comments(&comments.leading)?;
+            let mut directive_comments = comments.directives.iter();
             if let Some(version_directive) = version_directive {
                 implicit = false;
                 self.write_indicator("%YAML", true, false, false)?;
@@ -572,6 +591,9 @@ impl<'w> Emitter<'w> {
                 } else {
                     self.write_indicator("1.2", true, false, false)?;
                 }
+                if let Some(Some(comment)) = directive_comments.next() {
+                    self.write_inline_comments(core::slice::from_ref(comment))?;
+                }
                 self.write_indent()?;
             }
             if !tag_directives.is_empty() {
@@ -580,19 +602,26 @@ impl<'w> Emitter<'w> {
                     self.write_indicator("%TAG", true, false, false)?;
                     self.write_tag_handle(&tag_directive.handle)?;
                     self.write_tag_content(&tag_directive.prefix, true)?;
+                    if let Some(Some(comment)) = directive_comments.next() {
+                        self.write_inline_comments(core::slice::from_ref(comment))?;
+                    }
                     self.write_indent()?;
                 }
             }
             if Self::check_empty_document() {
                 implicit = false;
             }
-            if !implicit {
+            if implicit {
+                self.write_comments(&comments.inline)?;
+            } else {
                 self.write_indent()?;
                 self.write_indicator("---", true, false, false)?;
+                self.write_inline_comments(&comments.inline)?;
                 if self.canonical {
                     self.write_indent()?;
                 }
             }
+            self.trailing_comments.push(comments.trailing.clone());
             self.state = EmitterState::DocumentContent;
             self.open_ended = 0;
             return Ok(());
@@ -612,20 +641,28 @@ impl<'w> Emitter<'w> {
 
     fn emit_document_content(&mut self, event: &Event, analysis: &mut Analysis) -> Result<()> {
         self.states.push(EmitterState::DocumentEnd);
+        self.write_leading_comments(event)?;
         self.emit_node(event, true, false, false, false, analysis)
     }
 
     fn emit_document_end(&mut self, event: &Event) -> Result<()> {
-        if let EventData::DocumentEnd { implicit } = &event.data {
+        if let EventData::DocumentEnd { implicit, comments } = &event.data {
             let implicit = *implicit;
+            self.write_trailing_comments()?;
+            self.write_comments(&comments.leading)?;
             self.write_indent()?;
-            if !implicit {
+            if implicit {
+                self.write_comments(&comments.inline)?;
+                if self.open_ended == 0 {
+                    self.open_ended = 1;
+                }
+            } else {
                 self.write_indicator("...", true, false, false)?;
+                self.write_inline_comments(&comments.inline)?;
                 self.open_ended = 0;
                 self.write_indent()?;
-            } else if self.open_ended == 0 {
-                self.open_ended = 1;
             }
+            self.write_comments(&comments.trailing)?;
             self.flush()?;
             self.state = EmitterState::DocumentStart;
             self.tag_directives.clear();
@@ -646,7 +683,9 @@ impl<'w> Emitter<'w> {
             self.increase_indent(true, false);
             self.flow_level += 1;
         }
-        if let EventData::SequenceEnd = &event.data {
+        if let EventData::SequenceEnd { comments } = &event.data {
+            self.write_flow_inline_comments()?;
+            self.write_trailing_comments()?;
             self.flow_level -= 1;
             self.indent = self.indents.pop().unwrap();
             if self.canonical && !first {
@@ -654,15 +693,18 @@ impl<'w> Emitter<'w> {
                 self.write_indent()?;
             }
             self.write_indicator("]", false, false, false)?;
+            self.write_node_inline_comments(&comments.inline)?;
             self.state = self.states.pop().unwrap();
             return Ok(());
         }
         if !first {
             self.write_indicator(",", false, false, false)?;
+            self.write_flow_inline_comments()?;
         }
         if self.canonical || self.column > self.best_width {
             self.write_indent()?;
         }
+        self.write_leading_comments(event)?;
         self.states.push(EmitterState::FlowSequenceItem);
         self.emit_node(event, false, true, false, false, analysis)
     }
@@ -678,8 +720,10 @@ impl<'w> Emitter<'w> {
             self.increase_indent(true, false);
             self.flow_level += 1;
         }
-        if let EventData::MappingEnd = &event.data {
+        if let EventData::MappingEnd { comments } = &event.data {
             assert!(!self.indents.is_empty(), "self.indents should not be empty");
+            self.write_flow_inline_comments()?;
+            self.write_trailing_comments()?;
             self.flow_level -= 1;
             self.indent = self.indents.pop().unwrap();
             if self.canonical && !first {
@@ -687,16 +731,23 @@ impl<'w> Emitter<'w> {
                 self.write_indent()?;
             }
             self.write_indicator("}", false, false, false)?;
+            self.write_node_inline_comments(&comments.inline)?;
             self.state = self.states.pop().unwrap();
             return Ok(());
         }
         if !first {
             self.write_indicator(",", false, false, false)?;
+            self.write_flow_inline_comments()?;
         }
         if self.canonical || self.column > self.best_width {
             self.write_indent()?;
         }
+        self.write_leading_comments(event)?;
         if !self.canonical && self.check_simple_key(event, analysis) {
+            // The inline comments of a simple key end the preceding line.
+            if let Some(comments) = event.comments() {
+                self.write_inline_comments(&comments.inline)?;
+            }
             self.states.push(EmitterState::FlowMappingSimpleValue);
             self.emit_node(event, false, false, true, true, analysis)
         } else {
@@ -715,11 +766,13 @@ impl<'w> Emitter<'w> {
         if simple {
             self.write_indicator(":", false, false, false)?;
         } else {
+            self.write_flow_inline_comments()?;
             if self.canonical || self.column > self.best_width {
                 self.write_indent()?;
             }
             self.write_indicator(":", true, false, false)?;
         }
+        self.write_leading_comments(event)?;
         self.states.push(EmitterState::FlowMappingKey);
         self.emit_node(event, false, false, true, false, analysis)
     }
@@ -733,11 +786,13 @@ impl<'w> Emitter<'w> {
         if first {
             self.increase_indent(false, self.mapping_context && !self.indention);
         }
-        if let EventData::SequenceEnd = &event.data {
+        if let EventData::SequenceEnd { .. } = &event.data {
+            self.write_trailing_comments()?;
             self.indent = self.indents.pop().unwrap();
             self.state = self.states.pop().unwrap();
             return Ok(());
         }
+        self.write_leading_comments(event)?;
         self.write_indent()?;
         self.write_indicator("-", true, false, true)?;
         self.states.push(EmitterState::BlockSequenceItem);
@@ -753,13 +808,22 @@ impl<'w> Emitter<'w> {
         if first {
             self.increase_indent(false, false);
         }
-        if let EventData::MappingEnd = &event.data {
+        if let EventData::MappingEnd { .. } = &event.data {
+            self.write_trailing_comments()?;
             self.indent = self.indents.pop().unwrap();
             self.state = self.states.pop().unwrap();
             return Ok(());
         }
+        self.write_leading_comments(event)?;
+        let simple_key = self.check_simple_key(event, analysis);
+        if simple_key {
+            // The inline comments of a simple key are put on their own lines.
+            if let Some(comments) = event.comments() {
+                self.write_comments(&comments.inline)?;
+            }
+        }
         self.write_indent()?;
-        if self.check_simple_key(event, analysis) {
+        if simple_key {
             self.states.push(EmitterState::BlockMappingSimpleValue);
             self.emit_node(event, false, false, true, true, analysis)
         } else {
@@ -781,6 +845,14 @@ impl<'w> Emitter<'w> {
             self.write_indent()?;
             self.write_indicator(":", true, false, true)?;
         }
+        if event.comments().is_some_and(|c| !c.leading.is_empty()) {
+            // Put the value below its comments, indented like a nested
+            // collection.
+            self.indent += self.best_indent;
+            self.write_leading_comments(event)?;
+            self.write_indent()?;
+            self.indent -= self.best_indent;
+        }
         self.states.push(EmitterState::BlockMappingKey);
         self.emit_node(event, false, false, true, false, analysis)
     }
@@ -833,8 +905,18 @@ impl<'w> Emitter<'w> {
         self.process_anchor(anchor)?;
         self.process_tag(tag)?;
         self.increase_indent(true, false);
-        self.process_scalar(scalar)?;
+        let comments = event.comments().filter(|_| !self.simple_key_context);
+        let inline = comments.map_or(&[][..], |c| &c.inline[..]);
+        if matches!(scalar.style, ScalarStyle::Literal | ScalarStyle::Folded) {
+            self.process_scalar(scalar, inline)?;
+        } else {
+            self.process_scalar(scalar, &[])?;
+            self.write_node_inline_comments(inline)?;
+        }
         self.indent = self.indents.pop().unwrap();
+        if let Some(comments) = comments {
+            self.write_comments(&comments.trailing)?;
+        }
         self.state = self.states.pop().unwrap();
         Ok(())
     }
@@ -844,15 +926,19 @@ impl<'w> Emitter<'w> {
         self.process_anchor(anchor)?;
         self.process_tag(tag)?;
 
-        let EventData::SequenceStart { style, .. } = &event.data else {
+        let EventData::SequenceStart {
+            style, comments, ..
+        } = &event.data
+        else {
             unreachable!()
         };
 
-        if self.flow_level != 0
+        let flow = self.flow_level != 0
             || self.canonical
             || *style == SequenceStyle::Flow
-            || self.check_empty_sequence(event)
-        {
+            || self.check_empty_sequence(event);
+        self.write_collection_comments(comments, flow)?;
+        if flow {
             self.state = EmitterState::FlowSequenceFirstItem;
         } else {
             self.state = EmitterState::BlockSequenceFirstItem;
@@ -865,15 +951,19 @@ impl<'w> Emitter<'w> {
         self.process_anchor(anchor)?;
         self.process_tag(tag)?;
 
-        let EventData::MappingStart { style, .. } = &event.data else {
+        let EventData::MappingStart {
+            style, comments, ..
+        } = &event.data
+        else {
             unreachable!()
         };
 
-        if self.flow_level != 0
+        let flow = self.flow_level != 0
             || self.canonical
             || *style == MappingStyle::Flow
-            || self.check_empty_mapping(event)
-        {
+            || self.check_empty_mapping(event);
+        self.write_collection_comments(comments, flow)?;
+        if flow {
             self.state = EmitterState::FlowMappingFirstKey;
         } else {
             self.state = EmitterState::BlockMappingFirstKey;
@@ -881,6 +971,28 @@ impl<'w> Emitter<'w> {
         Ok(())
     }
 
+    /// Write the inline comments of a collection and remember its trailing
+    /// comments until the collection ends.
+    fn write_collection_comments(&mut self, comments: &Comments, flow: bool) -> Result<()> {
+        if !comments.inline.is_empty() {
+            self.write_inline_comments(&comments.inline)?;
+            if flow && self.flow_level == 0 {
+                // Indent the opening indicator like the content of a block
+                // collection.
+                let indent = self.indent;
+                self.indent = if indent < 0 {
+                    0
+                } else {
+                    indent + self.best_indent
+                };
+                self.write_indent()?;
+                self.indent = indent;
+            }
+        }
+        self.trailing_comments.push(comments.trailing.clone());
+        Ok(())
+    }
+
     fn check_empty_document() -> bool {
         false
     }
@@ -890,7 +1002,7 @@ impl<'w> Emitter<'w> {
             return false;
         }
         let start = matches!(event.data, EventData::SequenceStart { .. });
-        let end = matches!(self.events[0].data, EventData::SequenceEnd);
+        let end = matches!(self.events[0].data, EventData::SequenceEnd { .. });
         start && end
     }
 
@@ -899,7 +1011,7 @@ impl<'w> Emitter<'w> {
             return false;
         }
         let start = matches!(event.data, EventData::MappingStart { .. });
-        let end = matches!(self.events[0].data, EventData::MappingEnd);
+        let end = matches!(self.events[0].data, EventData::MappingEnd { .. });
         start && end
     }
 
@@ -1040,7 +1152,7 @@ impl<'w> Emitter<'w> {
         Ok(())
     }
 
-    fn process_scalar(&mut self, analysis: &ScalarAnalysis) -> Result<()> {
+    fn process_scalar(&mut self, analysis: &ScalarAnalysis, comments: &[String]) -> Result<()> {
         match analysis.style {
             ScalarStyle::Plain => self.write_plain_scalar(analysis.value, !self.simple_key_context),
             ScalarStyle::SingleQuoted => {
@@ -1049,8 +1161,8 @@ impl<'w> Emitter<'w> {
             ScalarStyle::DoubleQuoted => {
                 self.write_double_quoted_scalar(analysis.value, !self.simple_key_context)
             }
-            ScalarStyle::Literal => self.write_literal_scalar(analysis.value),
-            ScalarStyle::Folded => self.write_folded_scalar(analysis.value),
+            ScalarStyle::Literal => self.write_literal_scalar(analysis.value, comments),
+            ScalarStyle::Folded => self.write_folded_scalar(analysis.value, comments),
             ScalarStyle::Any => unreachable!("No scalar style chosen"),
         }
     }
@@ -1382,6 +1494,70 @@ impl<'w> Emitter<'w> {
         Ok(())
     }
 
+    /// Write comments on their own lines, at the current indentation.
+    fn write_comments(&mut self, comments: &[String]) -> Result<()> {
+        for comment in comments {
+            self.write_indent()?;
+            self.write_comment(comment)?;
+        }
+        Ok(())
+    }
+
+    /// Write comments at the end of the current line.
+    fn write_inline_comments(&mut self, comments: &[String]) -> Result<()> {
+        for comment in comments {
+            if !(self.whitespace && self.indention) {
+                self.put(' ')?;
+            }
+            self.write_comment(comment)?;
+        }
+        Ok(())
+    }
+
+    /// Write the inline comments of a node, or keep them until the ',' that
+    /// ends the node if it is in a flow collection.
+    fn write_node_inline_comments(&mut self, comments: &[String]) -> Result<()> {
+        if self.flow_level == 0 {
+            self.write_inline_comments(comments)
+        } else {
+            self.flow_inline_comments.extend_from_slice(comments);
+            Ok(())
+        }
+    }
+
+    /// Write the inline comments of the last entry of a flow collection.
+    fn write_flow_inline_comments(&mut self) -> Result<()> {
+        let comments = core::mem::take(&mut self.flow_inline_comments);
+        self.write_inline_comments(&comments)
+    }
+
+    /// Write the leading comments of a node event, if it has any.
+    fn write_leading_comments(&mut self, event: &Event) -> Result<()> {
+        if let Some(comments) = event.comments() {
+            self.write_comments(&comments.leading)?;
+        }
+        Ok(())
+    }
+
+    /// Write the trailing comments of the document or collection being closed.
+    fn write_trailing_comments(&mut self) -> Result<()> {
+        let comments = self.trailing_comments.pop().unwrap_or_default();
+        self.write_comments(&comments)
+    }
+
+    fn write_comment(&mut self, value: &str) -> Result<()> {
+        self.write_str("#")?;
+        self.write_str(value)?;
+        self.put_break()?;
+        self.whitespace = true;
+        self.indention = true;
+        if self.flow_level != 0 {
+            // Continue the flow collection at its indentation.
+            self.write_indent()?;
+        }
+        Ok(())
+    }
+
     fn write_indicator(
         &mut self,
         indicator: &str,
@@ -1698,10 +1874,15 @@ impl<'w> Emitter<'w> {
         Ok(())
     }
 
-    fn write_literal_scalar(&mut self, value: &str) -> Result<()> {
+    fn write_literal_scalar(&mut self, value: &str, comments: &[String]) -> Result<()> {
         let mut breaks = true;
         self.write_indicator("|", true, false, false)?;
         self.write_block_scalar_hints(value)?;
+        for comment in comments {
+            self.put(' ')?;
+            self.write_str("#")?;
+            self.write_str(comment)?;
+        }
         self.put_break()?;
         self.indention = true;
         self.whitespace = true;
@@ -1723,11 +1904,16 @@ impl<'w> Emitter<'w> {
         Ok(())
     }
 
-    fn write_folded_scalar(&mut self, value: &str) -> Result<()> {
+    fn write_folded_scalar(&mut self, value: &str, comments: &[String]) -> Result<()> {
         let mut breaks = true;
         let mut leading_spaces = true;
         self.write_indicator(">", true, false, false)?;
         self.write_block_scalar_hints(value)?;
+        for comment in comments {
+            self.put(' ')?;
+            self.write_str("#")?;
+            self.write_str(comment)?;
+        }
         self.put_break()?;
         self.indention = true;
         self.whitespace = true;
@@ -1786,21 +1972,32 @@ impl<'w> Emitter<'w> {
             return Ok(());
         }
 
-        let big_endian = match self.encoding {
+        match self.encoding {
             Encoding::Any | Encoding::Utf8 => {
                 unreachable!("unhandled encoding")
             }
-            Encoding::Utf16Le => false,
-            Encoding::Utf16Be => true,
-        };
-
-        for ch in self.buffer.encode_utf16() {
-            let bytes = if big_endian {
-                ch.to_be_bytes()
-            } else {
-                ch.to_le_bytes()
-            };
-            self.raw_buffer.extend(bytes);
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let big_endian = self.encoding == Encoding::Utf16Be;
+                for ch in self.buffer.encode_utf16() {
+                    let bytes = if big_endian {
+                        ch.to_be_bytes()
+                    } else {
+                        ch.to_le_bytes()
+                    };
+                    self.raw_buffer.extend(bytes);
+                }
+            }
+            Encoding::Utf32Le | Encoding::Utf32Be => {
+                let big_endian = self.encoding == Encoding::Utf32Be;
+                for ch in self.buffer.chars() {
+                    let bytes = if big_endian {
+                        u32::from(ch).to_be_bytes()
+                    } else {
+                        u32::from(ch).to_le_bytes()
+                    };
+                    self.raw_buffer.extend(bytes);
+                }
+            }
         }
 
         let to_emit = self.raw_buffer.as_slice();
@@ -1814,19 +2011,6 @@ impl<'w> Emitter<'w> {
         Ok(())
     }
 
-    pub(crate) fn reset_anchors(&mut self) {
-        self.anchors.clear();
-        self.last_anchor_id = 0;
-    }
-
-    pub(crate) fn anchor_node_sub(&mut self, index: i32) {
-        self.anchors[index as usize - 1].references += 1;
-        if self.anchors[index as usize - 1].references == 2 {
-            self.last_anchor_id += 1;
-            self.anchors[index as usize - 1].anchor = self.last_anchor_id;
-        }
-    }
-
     pub(crate) fn generate_anchor(anchor_id: i32) -> String {
         alloc::format!("id{anchor_id:03}")
     }
@@ -39,6 +39,15 @@ enum ErrorImpl {
     Composer(Problem),
     Emitter(&'static str),
     Io(std::io::Error),
+    Limit {
+        limit: Limit,
+        mark: Mark,
+    },
+    #[cfg_attr(not(feature = "serde"), allow(dead_code))]
+    Data {
+        message: String,
+        mark: Option<Mark>,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -49,6 +58,44 @@ pub enum ErrorKind {
     Composer,
     Emitter,
     Io,
+    /// A resource limit was exceeded, see [`Error::limit()`].
+    Limit,
+    /// The data does not match the Rust type that is serialized or
+    /// deserialized.
+    Data,
+}
+
+/// A resource limit of the parser.
+///
+/// See [`ParserLimits`](crate::ParserLimits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Limit {
+    /// The maximum nesting depth.
+    Depth,
+    /// The maximum length of a scalar.
+    ScalarLength,
+    /// The maximum number of events in a document.
+    Events,
+    /// The maximum number of anchors in a document.
+    Anchors,
+    /// The maximum number of aliases in a document.
+    Aliases,
+    /// The maximum size of the input.
+    InputBytes,
+}
+
+impl Limit {
+    fn problem(self) -> &'static str {
+        match self {
+            Limit::Depth => "exceeded the maximum nesting depth",
+            Limit::ScalarLength => "exceeded the maximum scalar length",
+            Limit::Events => "exceeded the maximum number of events in a document",
+            Limit::Anchors => "exceeded the maximum number of anchors in a document",
+            Limit::Aliases => "exceeded the maximum number of aliases in a document",
+            Limit::InputBytes => "exceeded the maximum input size",
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -115,6 +162,28 @@ impl Error {
         Self(Box::new(ErrorImpl::Emitter(problem)))
     }
 
+    pub(crate) fn limit_exceeded(limit: Limit, mark: Mark) -> Self {
+        Self(Box::new(ErrorImpl::Limit { limit, mark }))
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn data(message: String, mark: Option<Mark>) -> Self {
+        Self(Box::new(ErrorImpl::Data { message, mark }))
+    }
+
+    /// Set the mark of a data error that has none yet.
+    #[cfg(feature = "serde")]
+    pub(crate) fn with_mark(mut self, mark: Mark) -> Self {
+        if let ErrorImpl::Data {
+            mark: ref mut data_mark @ None,
+            ..
+        } = *self.0
+        {
+            *data_mark = Some(mark);
+        }
+        self
+    }
+
     pub fn kind(&self) -> ErrorKind {
         match &*self.0 {
             ErrorImpl::Reader { .. } => ErrorKind::Reader,
@@ -123,6 +192,16 @@ impl Error {
             ErrorImpl::Composer(_) => ErrorKind::Composer,
             ErrorImpl::Emitter(_) => ErrorKind::Emitter,
             ErrorImpl::Io(_) => ErrorKind::Io,
+            ErrorImpl::Limit { .. } => ErrorKind::Limit,
+            ErrorImpl::Data { .. } => ErrorKind::Data,
+        }
+    }
+
+    /// The resource limit that was exceeded, if any.
+    pub fn limit(&self) -> Option<Limit> {
+        match &*self.0 {
+            ErrorImpl::Limit { limit, .. } => Some(*limit),
+            _ => None,
         }
     }
 
@@ -132,12 +211,18 @@ impl Error {
             ErrorImpl::Scanner(ref p) | ErrorImpl::Parser(ref p) | ErrorImpl::Composer(ref p) => {
                 Some(p.problem_mark)
             }
+            ErrorImpl::Limit { mark, .. } => Some(*mark),
+            ErrorImpl::Data { mark, .. } => *mark,
         }
     }
 
     pub fn context_mark(&self) -> Option<Mark> {
         match &*self.0 {
-            ErrorImpl::Reader { .. } | ErrorImpl::Emitter(..) | ErrorImpl::Io(_) => None,
+            ErrorImpl::Reader { .. }
+            | ErrorImpl::Emitter(..)
+            | ErrorImpl::Io(_)
+            | ErrorImpl::Limit { .. }
+            | ErrorImpl::Data { .. } => None,
             ErrorImpl::Scanner(ref p) | ErrorImpl::Parser(ref p) | ErrorImpl::Composer(ref p) => {
                 if p.context.is_empty() {
                     None
@@ -155,12 +240,18 @@ impl Error {
                 p.problem
             }
             ErrorImpl::Io(_) => "I/O error",
+            ErrorImpl::Limit { limit, .. } => limit.problem(),
+            ErrorImpl::Data { .. } => "invalid data",
         }
     }
 
     pub fn context(&self) -> Option<&'static str> {
         match &*self.0 {
-            ErrorImpl::Reader { .. } | ErrorImpl::Emitter(..) | ErrorImpl::Io(_) => None,
+            ErrorImpl::Reader { .. }
+            | ErrorImpl::Emitter(..)
+            | ErrorImpl::Io(_)
+            | ErrorImpl::Limit { .. }
+            | ErrorImpl::Data { .. } => None,
             ErrorImpl::Scanner(ref p) | ErrorImpl::Parser(ref p) | ErrorImpl::Composer(ref p) => {
                 if p.context.is_empty() {
                     None
@@ -207,6 +298,8 @@ impl core::fmt::Display for ErrorKind {
             ErrorKind::Composer => "Composer",
             ErrorKind::Emitter => "Emitter",
             ErrorKind::Io => "I/O",
+            ErrorKind::Limit => "Limit",
+            ErrorKind::Data => "Data",
         })
     }
 }
@@ -242,6 +335,29 @@ impl core::fmt::Display for Error {
             }
             ErrorImpl::Emitter(problem) => write!(f, "{problem}"),
             ErrorImpl::Io(ref err) => write!(f, "{err}"),
+            ErrorImpl::Limit { limit, mark } => write!(f, "{mark}: {}", limit.problem()),
+            ErrorImpl::Data {
+                ref message,
+                mark: Some(mark),
+            } => write!(f, "{mark}: {message}"),
+            ErrorImpl::Data {
+                ref message,
+                mark: None,
+            } => write!(f, "{message}"),
         }
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::de::Error for Error {
+    fn custom<T: core::fmt::Display>(message: T) -> Self {
+        Self::data(message.to_string(), None)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::ser::Error for Error {
+    fn custom<T: core::fmt::Display>(message: T) -> Self {
+        Self::data(message.to_string(), None)
+    }
+}
@@ -1,21 +1,32 @@
+use alloc::borrow::Cow;
+
 use crate::{
     Encoding, MappingStyle, Mark, ScalarStyle, SequenceStyle, TagDirective, VersionDirective,
 };
 
 /// The event structure.
-#[derive(Debug, PartialEq)]
+///
+/// The anchors, tags and scalar values of the event are of type `S`, which is
+/// [`String`] unless the event is a [`BorrowedEvent`].
+#[derive(Clone, Debug, PartialEq)]
 #[non_exhaustive]
-pub struct Event {
+pub struct Event<S = String> {
     /// The event data.
-    pub data: EventData,
+    pub data: EventData<S>,
     /// The beginning of the event.
     pub start_mark: Mark,
     /// The end of the event.
     pub end_mark: Mark,
 }
 
-#[derive(Debug, PartialEq)]
-pub enum EventData {
+/// An event that borrows its anchors, tags and scalar values from an in-memory
+/// input where possible.
+///
+/// See [`Parser::parse_borrowed()`](crate::Parser::parse_borrowed).
+pub type BorrowedEvent<'input> = Event<Cow<'input, str>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventData<S = String> {
     /// The stream parameters (for YAML_STREAM_START_EVENT).
     StreamStart {
         /// The document encoding.
@@ -30,55 +41,224 @@ pub enum EventData {
         tag_directives: Vec<TagDirective>,
         /// Is the document indicator implicit?
         implicit: bool,
+        /// The comments attached to the document.
+        comments: Comments,
     },
     /// The document end parameters (for YAML_DOCUMENT_END_EVENT).
     DocumentEnd {
         implicit: bool,
+        /// The comments attached to the document end.
+        comments: Comments,
     },
     /// The alias parameters (for YAML_ALIAS_EVENT).
     Alias {
         /// The anchor.
-        anchor: String,
+        anchor: S,
     },
     /// The scalar parameters (for YAML_SCALAR_EVENT).
     Scalar {
         /// The anchor.
-        anchor: Option<String>,
+        anchor: Option<S>,
         /// The tag.
-        tag: Option<String>,
+        tag: Option<S>,
         /// The scalar value.
-        value: String,
+        value: S,
         /// Is the tag optional for the plain style?
         plain_implicit: bool,
         /// Is the tag optional for any non-plain style?
         quoted_implicit: bool,
         /// The scalar style.
         style: ScalarStyle,
+        /// The comments attached to the scalar.
+        comments: Comments,
     },
     /// The sequence parameters (for YAML_SEQUENCE_START_EVENT).
     SequenceStart {
         /// The anchor.
-        anchor: Option<String>,
+        anchor: Option<S>,
         /// The tag.
-        tag: Option<String>,
+        tag: Option<S>,
         /// Is the tag optional?
         implicit: bool,
         /// The sequence style.
         style: SequenceStyle,
+        /// The comments attached to the sequence.
+        comments: Comments,
+    },
+    /// The end of a sequence.
+    SequenceEnd {
+        /// The comments attached to the end of a flow sequence.
+        comments: Comments,
     },
-    SequenceEnd,
     /// The mapping parameters (for YAML_MAPPING_START_EVENT).
     MappingStart {
         /// The anchor.
-        anchor: Option<String>,
+        anchor: Option<S>,
         /// The tag.
-        tag: Option<String>,
+        tag: Option<S>,
         /// Is the tag optional?
         implicit: bool,
         /// The mapping style.
         style: MappingStyle,
+        /// The comments attached to the mapping.
+        comments: Comments,
+    },
+    /// The end of a mapping.
+    MappingEnd {
+        /// The comments attached to the end of a flow mapping.
+        comments: Comments,
     },
-    MappingEnd,
+}
+
+/// The comments attached to an event.
+///
+/// Only filled in by the parser when comments are preserved, see
+/// [`Parser::set_preserve_comments()`](crate::Parser::set_preserve_comments).
+/// Each comment is the text following the `#` indicator.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Comments {
+    /// Comments on their own lines before the node.
+    pub leading: Vec<String>,
+    /// Comments on the same line as the node.
+    ///
+    /// For the end of a flow collection, these follow the closing `]` or
+    /// `}`. In a flow collection, a comment after the `,` that ends an entry
+    /// is an inline comment of that entry.
+    pub inline: Vec<String>,
+    /// Comments on their own lines after the node.
+    ///
+    /// For collections and documents, these are written after the last
+    /// entry.
+    pub trailing: Vec<String>,
+    /// For a document start, the comment on the same line as each directive,
+    /// in the order of the directives: the `%YAML` directive, if any, then
+    /// the `%TAG` directives.
+    pub directives: Vec<Option<String>>,
+}
+
+impl Comments {
+    /// Are there no comments at all?
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty()
+            && self.inline.is_empty()
+            && self.trailing.is_empty()
+            && self.directives.iter().all(Option::is_none)
+    }
+}
+
+impl<S> Event<S> {
+    /// The comments attached to the event, if the event can carry any.
+    pub fn comments(&self) -> Option<&Comments> {
+        match &self.data {
+            EventData::DocumentStart { comments, .. }
+            | EventData::DocumentEnd { comments, .. }
+            | EventData::Scalar { comments, .. }
+            | EventData::SequenceStart { comments, .. }
+            | EventData::SequenceEnd { comments }
+            | EventData::MappingStart { comments, .. }
+            | EventData::MappingEnd { comments } => Some(comments),
+            _ => None,
+        }
+    }
+
+    /// Mutable access to the comments attached to the event, if the event can
+    /// carry any.
+    pub fn comments_mut(&mut self) -> Option<&mut Comments> {
+        match &mut self.data {
+            EventData::DocumentStart { comments, .. }
+            | EventData::DocumentEnd { comments, .. }
+            | EventData::Scalar { comments, .. }
+            | EventData::SequenceStart { comments, .. }
+            | EventData::SequenceEnd { comments }
+            | EventData::MappingStart { comments, .. }
+            | EventData::MappingEnd { comments } => Some(comments),
+            _ => None,
+        }
+    }
+}
+
+impl BorrowedEvent<'_> {
+    /// Copy the borrowed values of the event.
+    pub fn into_owned(self) -> Event {
+        Event {
+            data: self.data.into_owned(),
+            start_mark: self.start_mark,
+            end_mark: self.end_mark,
+        }
+    }
+}
+
+impl EventData<Cow<'_, str>> {
+    /// Copy the borrowed values of the event data.
+    pub fn into_owned(self) -> EventData {
+        match self {
+            EventData::StreamStart { encoding } => EventData::StreamStart { encoding },
+            EventData::StreamEnd => EventData::StreamEnd,
+            EventData::DocumentStart {
+                version_directive,
+                tag_directives,
+                implicit,
+                comments,
+            } => EventData::DocumentStart {
+                version_directive,
+                tag_directives,
+                implicit,
+                comments,
+            },
+            EventData::DocumentEnd { implicit, comments } => {
+                EventData::DocumentEnd { implicit, comments }
+            }
+            EventData::Alias { anchor } => EventData::Alias {
+                anchor: anchor.into_owned(),
+            },
+            EventData::Scalar {
+                anchor,
+                tag,
+                value,
+                plain_implicit,
+                quoted_implicit,
+                style,
+                comments,
+            } => EventData::Scalar {
+                anchor: anchor.map(Cow::into_owned),
+                tag: tag.map(Cow::into_owned),
+                value: value.into_owned(),
+                plain_implicit,
+                quoted_implicit,
+                style,
+                comments,
+            },
+            EventData::SequenceStart {
+                anchor,
+                tag,
+                implicit,
+                style,
+                comments,
+            } => EventData::SequenceStart {
+                anchor: anchor.map(Cow::into_owned),
+                tag: tag.map(Cow::into_owned),
+                implicit,
+                style,
+                comments,
+            },
+            EventData::SequenceEnd { comments } => EventData::SequenceEnd { comments },
+            EventData::MappingStart {
+                anchor,
+                tag,
+                implicit,
+                style,
+                comments,
+            } => EventData::MappingStart {
+                anchor: anchor.map(Cow::into_owned),
+                tag: tag.map(Cow::into_owned),
+                implicit,
+                style,
+                comments,
+            },
+            EventData::MappingEnd { comments } => EventData::MappingEnd { comments },
+        }
+    }
 }
 
 impl Event {
@@ -116,6 +296,7 @@ impl Event {
             version_directive,
             tag_directives,
             implicit,
+            comments: Comments::default(),
         })
     }
 
@@ -124,7 +305,10 @@ impl Event {
     /// The `implicit` argument is considered as a stylistic parameter and may be
     /// ignored by the emitter.
     pub fn document_end(implicit: bool) -> Self {
-        Self::new(EventData::DocumentEnd { implicit })
+        Self::new(EventData::DocumentEnd {
+            implicit,
+            comments: Comments::default(),
+        })
     }
 
     /// Create an ALIAS event.
@@ -166,6 +350,7 @@ impl Event {
             plain_implicit,
             quoted_implicit,
             style,
+            comments: Comments::default(),
         })
     }
 
@@ -195,12 +380,15 @@ impl Event {
             tag: tag_copy,
             implicit,
             style,
+            comments: Comments::default(),
         })
     }
 
     /// Create a SEQUENCE-END event.
     pub fn sequence_end() -> Self {
-        Self::new(EventData::SequenceEnd)
+        Self::new(EventData::SequenceEnd {
+            comments: Comments::default(),
+        })
     }
 
     /// Create a MAPPING-START event.
@@ -230,11 +418,14 @@ impl Event {
             tag: tag_copy,
             implicit,
             style,
+            comments: Comments::default(),
         })
     }
 
     /// Create a MAPPING-END event.
     pub fn mapping_end() -> Self {
-        Self::new(EventData::MappingEnd)
+        Self::new(EventData::MappingEnd {
+            comments: Comments::default(),
+        })
     }
 }
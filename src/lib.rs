@@ -26,6 +26,9 @@ extern crate alloc;
 #[macro_use]
 mod macros;
 
+mod canonical;
+#[cfg(feature = "serde")]
+mod de;
 mod document;
 mod emitter;
 mod error;
@@ -33,15 +36,27 @@ mod event;
 mod parser;
 mod reader;
 mod scanner;
+mod schema;
+#[cfg(feature = "serde")]
+mod ser;
+mod stream;
 mod token;
+mod visit;
 
+#[cfg(feature = "serde")]
+pub use crate::de::*;
 pub use crate::document::*;
 pub use crate::emitter::*;
 pub use crate::error::*;
 pub use crate::event::*;
 pub use crate::parser::*;
 pub use crate::scanner::*;
+pub use crate::schema::*;
+#[cfg(feature = "serde")]
+pub use crate::ser::*;
+pub use crate::stream::*;
 pub use crate::token::*;
+pub use crate::visit::*;
 
 pub(crate) const INPUT_RAW_BUFFER_SIZE: usize = 16384;
 pub(crate) const INPUT_BUFFER_SIZE: usize = INPUT_RAW_BUFFER_SIZE;
@@ -59,6 +74,8 @@ pub const INT_TAG: &str = "tag:yaml.org,2002:int";
 pub const FLOAT_TAG: &str = "tag:yaml.org,2002:float";
 /// The tag `!!timestamp` for date and time values.
 pub const TIMESTAMP_TAG: &str = "tag:yaml.org,2002:timestamp";
+/// The tag `!!merge` for the merge key `<<`.
+pub const MERGE_TAG: &str = "tag:yaml.org,2002:merge";
 
 /// The tag `!!seq` is used to denote sequences.
 pub const SEQ_TAG: &str = "tag:yaml.org,2002:seq";
@@ -101,10 +118,14 @@ pub enum Encoding {
     Any = 0,
     /// The default UTF-8 encoding.
     Utf8 = 1,
-    /// The UTF-16-LE encoding with BOM.
+    /// The UTF-16-LE encoding.
     Utf16Le = 2,
-    /// The UTF-16-BE encoding with BOM.
+    /// The UTF-16-BE encoding.
     Utf16Be = 3,
+    /// The UTF-32-LE encoding.
+    Utf32Le = 4,
+    /// The UTF-32-BE encoding.
+    Utf32Be = 5,
 }
 
 /// Line break type.
@@ -167,10 +188,31 @@ pub enum MappingStyle {
 
 #[cfg(test)]
 mod tests {
+    use alloc::borrow::Cow;
     use alloc::vec::Vec;
+    use core::fmt::Write as _;
 
     use super::*;
 
+    /// Load a document with the YAML 1.2 core schema.
+    fn load(input: &str) -> Document {
+        let mut parser = Parser::new();
+        parser.set_input_slice(input.as_bytes());
+        parser.set_schema(Schema::Core);
+        Document::load(&mut parser).unwrap()
+    }
+
+    /// Dump a document for readers with the YAML 1.2 core schema.
+    fn dump(doc: &Document) -> String {
+        let mut output = Vec::new();
+        let mut emitter = Emitter::new();
+        emitter.set_output_string(&mut output);
+        emitter.set_schema(Schema::Core);
+        doc.dump(&mut emitter).unwrap();
+        drop(emitter);
+        String::from_utf8(output).unwrap()
+    }
+
     #[test]
     fn sanity() {
         const SANITY_INPUT: &str = r#"unicode: "Sosa did fine.\u263A"
@@ -383,6 +425,1275 @@ c: true";
         );
     }
 
+    #[test]
+    fn scanner_comments() {
+        const INPUT: &str = "# head\na: 1 # one\n# tail\n";
+        let mut scanner = Scanner::new();
+        scanner.set_preserve_comments(true);
+        let mut read_in = INPUT.as_bytes();
+        scanner.set_input(&mut read_in);
+        let tokens = scanner.collect::<Result<Vec<_>, _>>().unwrap();
+        let comments = tokens
+            .iter()
+            .filter_map(|token| match &token.data {
+                TokenData::Comment { value, inline } => {
+                    Some((value.as_str(), *inline, token.start_mark, token.end_mark))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mark = |index, line, column| Mark {
+            index,
+            line,
+            column,
+        };
+        assert_eq!(
+            comments,
+            [
+                (" head", false, mark(0, 0, 0), mark(6, 0, 6)),
+                (" one", true, mark(12, 1, 5), mark(17, 1, 10)),
+                (" tail", false, mark(18, 2, 0), mark(24, 2, 6)),
+            ]
+        );
+        assert!(matches!(tokens[1].data, TokenData::Comment { .. }));
+        assert!(matches!(tokens[2].data, TokenData::BlockMappingStart));
+    }
+
+    #[test]
+    fn comments_roundtrip() {
+        const INPUT: &str = "# head
+a: 1 # one
+b: # bee
+  - x # ex
+  - y
+  # end of b
+c:
+  # before d
+  d: | # lit
+    text
+# tail
+";
+        fn parse(input: &str) -> Vec<Event> {
+            let mut parser = Parser::new();
+            parser.set_preserve_comments(true);
+            let mut read_in = input.as_bytes();
+            parser.set_input_string(&mut read_in);
+            parser.collect::<Result<Vec<_>, _>>().unwrap()
+        }
+
+        fn emit(events: Vec<Event>) -> String {
+            let mut emitter = Emitter::new();
+            let mut output = Vec::new();
+            emitter.set_output(&mut output);
+            for event in events {
+                emitter.emit(event).unwrap();
+            }
+            drop(emitter);
+            String::from_utf8(output).expect("invalid UTF-8")
+        }
+
+        let events = parse(INPUT);
+        let y = events
+            .iter()
+            .find(|event| matches!(&event.data, EventData::Scalar { value, .. } if value == "y"))
+            .unwrap();
+        let comments = y.comments().unwrap();
+        assert!(comments.leading.is_empty());
+        assert!(comments.inline.is_empty());
+        assert_eq!(comments.trailing, [" end of b"]);
+        assert_eq!(emit(events), INPUT);
+
+        // Comments after the end of a flow collection, after the ',' of a flow
+        // entry, and after directives.
+        for input in [
+            "- [a, b] # c\n- d\n",
+            "a: {b: 1} # c\n",
+            "[a, # c\n  b]\n",
+            "{a: 1, # c\n  b: [[d], # e\n    f] # g\n  }\n",
+            "%YAML 1.2 # v\n%TAG !e! tag:e, # t\n--- # d\na\n",
+        ] {
+            assert_eq!(emit(parse(input)), input);
+        }
+        let events = parse("[a, # c\n b] # d\n");
+        assert_eq!(events[3].comments().unwrap().inline, [" c"]);
+        assert!(events[4].comments().unwrap().is_empty());
+        assert_eq!(events[5].comments().unwrap().inline, [" d"]);
+        let events = parse("%TAG !e! tag:e, # t\n%YAML 1.2\n--- # d\na\n");
+        let comments = events[1].comments().unwrap();
+        assert_eq!(comments.directives, [None, Some(String::from(" t"))]);
+        assert_eq!(comments.inline, [" d"]);
+    }
+
+    #[test]
+    fn input_slice_matches_reader() {
+        let inputs: [&[u8]; 5] = [
+            b"key: value\r\nlist:\n  - a b  # comment\n  - \"c\\td\"\n",
+            "\u{feff}caf\u{e9}: \u{1f600}\u{85}  more\u{2028}x\n".as_bytes(),
+            b"[a, {b: c}]\n--- |\n  literal\n...\n",
+            b"a: \x01\n",
+            b"a: \xff\n",
+        ];
+        for input in inputs {
+            let mut read_in = input;
+            let mut scanner = Scanner::new();
+            scanner.set_input_string(&mut read_in);
+            let expected = scanner
+                .map(|token| format!("{token:?}"))
+                .take(100)
+                .collect::<Vec<_>>();
+
+            let mut scanner = Scanner::new();
+            scanner.set_input_slice(input);
+            let tokens = scanner
+                .map(|token| format!("{token:?}"))
+                .take(100)
+                .collect::<Vec<_>>();
+            assert_eq!(tokens, expected);
+        }
+    }
+
+    #[test]
+    fn parse_borrowed_from_slice() {
+        let input =
+            b"&a !<tag:x> plain: 'quoted'\nb: \"esc\\t\"\nc: >\n  folded\nd: *a\ne: two\n  lines\n";
+        let mut parser = Parser::new();
+        parser.set_input_slice(input);
+        let mut scalars = Vec::new();
+        loop {
+            let event = parser.parse_borrowed().unwrap();
+            match event.data {
+                EventData::Scalar {
+                    anchor, tag, value, ..
+                } => scalars.push((anchor, tag, value)),
+                EventData::Alias { anchor } => {
+                    assert!(matches!(anchor, Cow::Borrowed("a")));
+                }
+                EventData::StreamEnd => break,
+                _ => {}
+            }
+        }
+        let values: Vec<_> = scalars
+            .iter()
+            .map(|(_, _, value)| (value.as_ref(), matches!(value, Cow::Borrowed(_))))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                ("plain", true),
+                ("quoted", true),
+                ("b", true),
+                ("esc\t", false),
+                ("c", true),
+                ("folded\n", false),
+                ("d", true),
+                ("e", true),
+                ("two lines", false),
+            ]
+        );
+        assert!(matches!(scalars[0].0, Some(Cow::Borrowed("a"))));
+        assert!(matches!(scalars[0].1, Some(Cow::Borrowed("tag:x"))));
+
+        let mut read_in = &input[..];
+        let mut parser = Parser::new();
+        parser.set_input_string(&mut read_in);
+        let event = parser.nth(3).unwrap().unwrap();
+        assert!(matches!(
+            event.data,
+            EventData::Scalar { anchor: Some(ref anchor), .. } if anchor == "a"
+        ));
+    }
+
+    #[test]
+    fn parser_limits() {
+        let no_limits = ParserLimits::default();
+        let cases: [(&[u8], ParserLimits, Limit); 6] = [
+            (
+                b"a: [[[b]]]\n",
+                ParserLimits {
+                    max_depth: Some(3),
+                    ..no_limits
+                },
+                Limit::Depth,
+            ),
+            (
+                b"a: bcd\n",
+                ParserLimits {
+                    max_scalar_length: Some(2),
+                    ..no_limits
+                },
+                Limit::ScalarLength,
+            ),
+            (
+                b"[a, b]\n",
+                ParserLimits {
+                    max_events_per_document: Some(4),
+                    ..no_limits
+                },
+                Limit::Events,
+            ),
+            (
+                b"- &a a\n- &b b\n",
+                ParserLimits {
+                    max_anchors: Some(1),
+                    ..no_limits
+                },
+                Limit::Anchors,
+            ),
+            (
+                b"- &a a\n- *a\n- *a\n",
+                ParserLimits {
+                    max_aliases: Some(1),
+                    ..no_limits
+                },
+                Limit::Aliases,
+            ),
+            (
+                b"a: b\n",
+                ParserLimits {
+                    max_input_bytes: Some(4),
+                    ..no_limits
+                },
+                Limit::InputBytes,
+            ),
+        ];
+        for (input, limits, limit) in cases {
+            let mut parser = Parser::new();
+            parser.set_input_slice(input);
+            assert!(parser.all(|event| event.is_ok()));
+
+            for slice in [true, false] {
+                let mut read_in = input;
+                let mut parser = Parser::new();
+                if slice {
+                    parser.set_input_slice(input);
+                } else {
+                    parser.set_input_string(&mut read_in);
+                }
+                parser.set_limits(limits);
+                let err = parser.find_map(Result::err).unwrap();
+                assert_eq!(err.kind(), ErrorKind::Limit);
+                assert_eq!(err.limit(), Some(limit));
+            }
+        }
+
+        // The scalar length is checked while scanning, before an endless
+        // scalar is read into memory.
+        for prefix in [&b"- "[..], b"- '", b"- \"", b"- |\n  "] {
+            let mut input =
+                std::io::BufReader::new(std::io::Read::chain(prefix, std::io::repeat(b'a')));
+            let mut parser = Parser::new();
+            parser.set_input(&mut input);
+            parser.set_limits(ParserLimits {
+                max_scalar_length: Some(1 << 16),
+                ..no_limits
+            });
+            let err = parser.find_map(Result::err).unwrap();
+            assert_eq!(err.limit(), Some(Limit::ScalarLength));
+            assert_eq!(err.problem_mark().unwrap().column, 2);
+        }
+    }
+
+    #[test]
+    fn alias_expansion_limit() {
+        const LAUGHS: &[u8] = b"a: &a [lol, lol, lol]\nb: &b [*a, *a, *a]\nc: &c [*b, *b, *b]\n";
+        let load = |input, max| {
+            let mut parser = Parser::new();
+            parser.set_input_slice(input);
+            parser.set_limits(ParserLimits {
+                max_alias_expansion: max,
+                ..ParserLimits::default()
+            });
+            Document::load(&mut parser)
+        };
+        // 4 nodes for the mapping and its keys, 4 for `a`, 13 for `b` and 40
+        // for `c`.
+        assert!(load(LAUGHS, None).is_ok());
+        assert!(load(LAUGHS, Some(61)).is_ok());
+        let err = load(LAUGHS, Some(60)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Composer);
+        assert_eq!(err.problem(), "exceeded the alias expansion limit");
+
+        assert!(load(b"&a [*a]", None).is_ok());
+        let err = load(b"&a [*a]", Some(64)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Composer);
+    }
+
+    #[test]
+    fn schemas() {
+        let cases = [
+            // value, JSON, core, YAML 1.1
+            ("null", Some(NULL_TAG), NULL_TAG, NULL_TAG),
+            ("~", None, NULL_TAG, NULL_TAG),
+            ("True", None, BOOL_TAG, BOOL_TAG),
+            ("yes", None, STR_TAG, BOOL_TAG),
+            ("-12", Some(INT_TAG), INT_TAG, INT_TAG),
+            ("012", None, INT_TAG, INT_TAG),
+            ("0o17", None, INT_TAG, STR_TAG),
+            ("0x1F", None, INT_TAG, INT_TAG),
+            ("0b101", None, STR_TAG, INT_TAG),
+            ("1_000", None, STR_TAG, INT_TAG),
+            ("190:20:30", None, STR_TAG, INT_TAG),
+            ("1.5e3", Some(FLOAT_TAG), FLOAT_TAG, STR_TAG),
+            ("1.5e+3", Some(FLOAT_TAG), FLOAT_TAG, FLOAT_TAG),
+            ("-.inf", None, FLOAT_TAG, FLOAT_TAG),
+            (".NaN", None, FLOAT_TAG, FLOAT_TAG),
+            ("2001-12-14t21:59:43.10-05:00", None, STR_TAG, TIMESTAMP_TAG),
+            ("2002-12-14", None, STR_TAG, TIMESTAMP_TAG),
+            ("text", None, STR_TAG, STR_TAG),
+        ];
+        for (value, json, core, yaml11) in cases {
+            assert_eq!(Schema::Failsafe.resolve(value), Some(STR_TAG), "{value}");
+            assert_eq!(Schema::Json.resolve(value), json, "{value}");
+            assert_eq!(Schema::Core.resolve(value), Some(core), "{value}");
+            assert_eq!(Schema::Yaml11.resolve(value), Some(yaml11), "{value}");
+        }
+
+        let input = "[1, '1', ~, !!str 2, !!int '3', text]\n";
+        let mut parser = Parser::new();
+        parser.set_input_slice(input.as_bytes());
+        parser.set_schema(Schema::Core);
+        let doc = Document::load(&mut parser).unwrap();
+        let tags: Vec<_> = doc.nodes[1..]
+            .iter()
+            .map(|node| node.tag.as_deref().unwrap())
+            .collect();
+        assert_eq!(
+            tags,
+            [INT_TAG, STR_TAG, NULL_TAG, STR_TAG, INT_TAG, STR_TAG]
+        );
+
+        // Tags are only left out where the schema of the reader resolves the
+        // plain value to the same tag, so that the output round-trips.
+        for (schema, expected) in [
+            (
+                Schema::Failsafe,
+                "[!!int 1, '1', !!null ~, 2, !!int '3', text]\n",
+            ),
+            (Schema::Core, "[1, '1', ~, '2', 3, text]\n"),
+        ] {
+            let mut output = Vec::new();
+            let mut emitter = Emitter::new();
+            emitter.set_output_string(&mut output);
+            emitter.set_schema(schema);
+            doc.dump(&mut emitter).unwrap();
+            drop(emitter);
+            assert_eq!(core::str::from_utf8(&output).unwrap(), expected);
+
+            let mut parser = Parser::new();
+            parser.set_input_slice(&output);
+            parser.set_schema(schema);
+            assert!(Document::load(&mut parser).unwrap().semantic_eq(&doc));
+        }
+
+        let mut parser = Parser::new();
+        parser.set_input_slice(b"[1, text]");
+        parser.set_schema(Schema::Json);
+        let err = Document::load(&mut parser).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Composer);
+        assert_eq!(err.problem_mark().map(|mark| mark.column), Some(4));
+    }
+
+    #[test]
+    fn node_accessors() {
+        let input =
+            "[text, '1', ~, True, off, -0x1f, 0o17, 18446744073709551615, 1.5e+3, -.inf, !!float 2]";
+        let mut parser = Parser::new();
+        parser.set_input_slice(input.as_bytes());
+        parser.set_schema(Schema::Yaml11);
+        let doc = Document::load(&mut parser).unwrap();
+        let node = |index: usize| &doc.nodes[index + 1];
+
+        assert_eq!(node(0).as_str(), Some("text"));
+        assert_eq!(node(1).as_str(), Some("1"));
+        assert_eq!(node(1).as_i64(), None);
+        assert!(node(2).is_null());
+        assert!(!node(0).is_null());
+        assert_eq!(node(3).as_bool(), Some(true));
+        assert_eq!(node(4).as_bool(), Some(false));
+        assert_eq!(node(4).as_str(), None);
+        // `-0x1f` is an integer in YAML 1.1 only, and `0o17` in YAML 1.2 only.
+        assert_eq!(node(5).as_i64(), Some(-31));
+        assert_eq!(node(6).as_i64(), None);
+        assert_eq!(node(7).as_u64(), Some(u64::MAX));
+        assert_eq!(node(7).as_i64(), None);
+        assert_eq!(node(8).as_f64(), Some(1500.0));
+        assert_eq!(node(9).as_f64(), Some(f64::NEG_INFINITY));
+        assert_eq!(node(10).as_f64(), Some(2.0));
+        assert_eq!(node(8).as_i64(), None);
+        assert_eq!(doc.nodes[0].as_str(), None);
+
+        let mut parser = Parser::new();
+        parser.set_input_slice(b"[012, 0b11, 1_000, -190:20:30, 1_000.5, 1:30.5]");
+        parser.set_schema(Schema::Yaml11);
+        let doc = Document::load(&mut parser).unwrap();
+        assert_eq!(doc.nodes[1].as_i64(), Some(10));
+        assert_eq!(doc.nodes[2].as_i64(), Some(3));
+        assert_eq!(doc.nodes[3].as_u64(), Some(1000));
+        assert_eq!(doc.nodes[4].as_i64(), Some(-685_230));
+        assert_eq!(doc.nodes[5].as_f64(), Some(1000.5));
+        assert_eq!(doc.nodes[6].as_f64(), Some(90.5));
+
+        let mut parser = Parser::new();
+        parser.set_input_slice(b"[0o17, 0x1F, -12, .NaN, 012, !!bool yes, !!bool True]");
+        parser.set_schema(Schema::Core);
+        let doc = Document::load(&mut parser).unwrap();
+        assert_eq!(doc.nodes[1].as_i64(), Some(15));
+        assert_eq!(doc.nodes[2].as_u64(), Some(31));
+        assert_eq!(doc.nodes[3].as_i64(), Some(-12));
+        assert_eq!(doc.nodes[3].as_u64(), None);
+        assert!(doc.nodes[4].as_f64().is_some_and(f64::is_nan));
+        assert_eq!(doc.nodes[5].as_i64(), Some(12));
+        // `yes` is a boolean in YAML 1.1 only.
+        assert_eq!(doc.nodes[6].as_bool(), None);
+        assert_eq!(doc.nodes[7].as_bool(), Some(true));
+    }
+
+    #[test]
+    fn merge_keys() {
+        let load = |input: &str| {
+            let mut parser = Parser::new();
+            parser.set_input_slice(input.as_bytes());
+            let mut doc = Document::load(&mut parser).unwrap();
+            doc.apply_merge_keys().map(|()| doc)
+        };
+        let pairs = |doc: &Document, index: NodeId| {
+            let NodeData::Mapping { pairs, .. } = &doc.get_node(index).unwrap().data else {
+                panic!("expected a mapping");
+            };
+            pairs
+                .iter()
+                .map(|pair| {
+                    let key = doc.get_node(pair.key).unwrap().as_str().unwrap();
+                    let value = doc.get_node(pair.value).unwrap().as_str().unwrap();
+                    format!("{key}={value}")
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let doc = load(
+            "- &a {x: 1, y: 1}\n\
+             - &b {y: 2, z: 2, <<: {w: 2}}\n\
+             - {<<: [*a, *b], x: 3}\n\
+             - {'<<': *a}\n",
+        )
+        .unwrap();
+        let NodeData::Sequence { items, .. } = &doc.nodes[0].data else {
+            panic!("expected a sequence");
+        };
+        assert_eq!(pairs(&doc, items[1]), ["w=2", "y=2", "z=2"]);
+        assert_eq!(pairs(&doc, items[2]), ["y=1", "w=2", "z=2", "x=3"]);
+        // A quoted `<<` is an ordinary key.
+        let NodeData::Mapping { pairs, .. } = &doc.get_node(items[3]).unwrap().data else {
+            panic!("expected a mapping");
+        };
+        assert_eq!(pairs.len(), 1);
+        // So is a `<<` with an explicit tag.
+        let doc = load("!!str <<: {a: 1}").unwrap();
+        let NodeData::Mapping { pairs, .. } = &doc.nodes[0].data else {
+            panic!("expected a mapping");
+        };
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(doc.get_node(pairs[0].key).unwrap().as_str(), Some("<<"));
+
+        // A merge key that was not applied is dumped as a plain `<<` again.
+        assert_eq!(dump(&self::load("<<: {a: 1}\n")), "<<: {a: 1}\n");
+
+        let err = load("base: &b [1]\nderived: {<<: *b}").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Composer);
+        assert_eq!(err.problem(), "expected a mapping for merging");
+        let err = load("{<<: 1}").unwrap_err();
+        assert_eq!(
+            err.problem(),
+            "expected a mapping or a sequence of mappings for merging"
+        );
+        let err = load("&a {<<: *a}").unwrap_err();
+        assert_eq!(err.problem(), "found recursive merge");
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let load = |input: &str, strict: bool| {
+            let mut parser = Parser::new();
+            parser.set_input_slice(input.as_bytes());
+            parser.set_schema(Schema::Core);
+            parser.set_strict(strict);
+            Document::load(&mut parser)
+        };
+
+        let err = load("a: 1\nb: 2\na: 3\n", true).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Composer);
+        assert_eq!(err.problem(), "second occurrence");
+        assert_eq!(err.problem_mark().map(|mark| mark.line), Some(2));
+        assert_eq!(err.context(), Some("found duplicate key; first occurrence"));
+        assert_eq!(err.context_mark().map(|mark| mark.line), Some(0));
+        assert!(load("a: 1\na: 3\n", false).is_ok());
+
+        // Complex keys, with mappings in any order.
+        assert!(load("? {x: 1, y: [2]}\n: a\n? {y: [2], x: 1}\n: b\n", true).is_err());
+        assert!(load("? {x: 1, y: [2]}\n: a\n? {y: [3], x: 1}\n: b\n", true).is_ok());
+        // Keys with different resolved tags are different.
+        assert!(load("{1: a, '1': b, !!str 1: c}", true).is_err());
+        assert!(load("{1: a, '1': b}", true).is_ok());
+        assert!(load("{&a [*a]: 1, &b [*b]: 2}", true).is_err());
+
+        // Equal keys built from different shared nodes are compared once per
+        // pair of nodes, not once per path through them.
+        let mut input = String::from("x0: &x0 [a]\ny0: &y0 [a]\n");
+        for n in 1..=40 {
+            for chain in ["x", "y"] {
+                writeln!(
+                    input,
+                    "{chain}{n}: &{chain}{n} [*{chain}{m}, *{chain}{m}]",
+                    m = n - 1
+                )
+                .unwrap();
+            }
+        }
+        input += "? *x40\n: 1\n? *y40\n: 2\n";
+        let err = load(&input, true).unwrap_err();
+        assert_eq!(err.problem(), "second occurrence");
+        let mut doc = load(&input, false).unwrap();
+        let other = doc.clone();
+        assert!(doc.semantic_eq(&other));
+        assert!(doc.diff(&other).is_empty());
+        doc.merge_from(&other, MergeStrategy::Replace);
+
+        // Keys of the same shape are only compared if they are equal.
+        let mut input = String::new();
+        for n in 0..10000 {
+            writeln!(input, "? [[[k{n}]]]\n: 1").unwrap();
+        }
+        assert!(load(&input, true).is_ok());
+        input += "? [[[k0]]]\n: 2\n";
+        assert!(load(&input, true).is_err());
+
+        // Deeply nested keys do not overflow the stack.
+        let key = format!("{}x", "- ".repeat(100_000));
+        assert!(load(&format!("? {key}\n: 1\n? {key}\n: 2\n"), true).is_err());
+        assert!(load(&format!("? {key}\n: 1\n? - {key}\n: 2\n"), true).is_ok());
+    }
+
+    #[test]
+    fn anchor_redefinition() {
+        const INPUT: &[u8] = b"- &a first\n- *a\n- &a second\n- *a\n";
+        let mut parser = Parser::new();
+        parser.set_input_slice(INPUT);
+        let err = Document::load(&mut parser).unwrap_err();
+        assert_eq!(
+            err.context(),
+            Some("found duplicate anchor; first occurrence")
+        );
+
+        let mut parser = Parser::new();
+        parser.set_input_slice(INPUT);
+        parser.set_allow_anchor_redefinition(true);
+        let doc = Document::load(&mut parser).unwrap();
+        let NodeData::Sequence { items, .. } = &doc.nodes[0].data else {
+            panic!("expected a sequence");
+        };
+        let values: Vec<_> = items
+            .iter()
+            .map(|item| doc.get_node(*item).unwrap().as_str().unwrap())
+            .collect();
+        assert_eq!(values, ["first", "first", "second", "second"]);
+    }
+
+    #[test]
+    fn node_ids() {
+        let mut doc = Document::new(None, &[], true, true);
+        let root = doc.add_mapping(None, MappingStyle::Block);
+        let key = doc.add_scalar(None, "a", ScalarStyle::Plain);
+        let inner = doc.add_mapping(None, MappingStyle::Flow);
+        let shared = doc.add_sequence(None, SequenceStyle::Flow);
+        let item = doc.add_scalar(None, "1", ScalarStyle::Plain);
+        doc.append_sequence_item(shared, item);
+        let inner_key = doc.add_scalar(None, "c", ScalarStyle::Plain);
+        doc.yaml_document_append_mapping_pair(inner, inner_key, shared);
+        doc.yaml_document_append_mapping_pair(root, key, inner);
+        let other = doc.add_scalar(None, "b", ScalarStyle::Plain);
+        doc.yaml_document_append_mapping_pair(root, other, shared);
+        assert_eq!(doc.get_node(item).unwrap().as_str(), Some("1"));
+        assert!(doc.get_node(NodeId::from_index(doc.nodes.len())).is_none());
+
+        // The shared sequence is nested below the root mapping, and aliased.
+        let mut output = Vec::new();
+        let mut emitter = Emitter::new();
+        emitter.set_output_string(&mut output);
+        doc.dump(&mut emitter).unwrap();
+        assert_eq!(
+            core::str::from_utf8(&output).unwrap(),
+            "a: {c: &id001 [1]}\nb: *id001\n"
+        );
+    }
+
+    #[test]
+    fn paths() {
+        const INPUT: &[u8] =
+            b"spec:\n  containers:\n  - name: app\n    image: app:1\n  - name: db\n\
+                               a/b~c: 1\n0: zero\n";
+        let mut parser = Parser::new();
+        parser.set_input_slice(INPUT);
+        let mut doc = Document::load(&mut parser).unwrap();
+
+        let image = doc.get_path(&["spec", "containers", "0", "image"]).unwrap();
+        assert_eq!(image.as_str(), Some("app:1"));
+        assert_eq!(
+            doc.get_path_id(&["spec", "containers", "1", "name"]),
+            doc.get_pointer_id("/spec/containers/1/name")
+        );
+        assert!(doc.get_path(&["spec", "containers", "2"]).is_none());
+        assert!(doc.get_path(&["spec", "containers", "01"]).is_none());
+        assert!(doc.get_path(&["spec", "missing"]).is_none());
+        assert!(doc.get_path(&["0", "x"]).is_none());
+        assert_eq!(doc.get_path(&["0"]).unwrap().as_str(), Some("zero"));
+        assert_eq!(doc.get_path_id(&[]), doc.get_pointer_id(""));
+        assert!(doc.get_pointer("/a~1b~0c").is_some());
+        assert!(doc.get_pointer("/a~2b").is_none());
+        assert!(doc.get_pointer("spec").is_none());
+
+        let node = doc.get_pointer_mut("/spec/containers/1/name").unwrap();
+        if let NodeData::Scalar { value, .. } = &mut node.data {
+            *value = String::from("cache");
+        }
+        let name = doc
+            .get_path_mut(&["spec", "containers", "1", "name"])
+            .unwrap();
+        assert_eq!(name.as_str(), Some("cache"));
+    }
+
+    #[test]
+    fn mutation() {
+        let mut parser = Parser::new();
+        parser.set_input_slice(b"name: app\nports: [80, 443]\ndebug: true\n");
+        let mut doc = Document::load(&mut parser).unwrap();
+        let root = doc.get_path_id(&[]).unwrap();
+        let ports = doc.get_path_id(&["ports"]).unwrap();
+
+        let removed = doc.remove_sequence_item(ports, 0).unwrap();
+        assert_eq!(doc.get_node(removed).unwrap().as_str(), Some("80"));
+        assert!(doc.remove_sequence_item(ports, 5).is_none());
+        let port = doc.add_scalar(None, "8080", ScalarStyle::Plain);
+        doc.insert_sequence_item(ports, 1, port);
+
+        let pair = doc.remove_mapping_pair(root, "debug").unwrap();
+        assert_eq!(doc.get_node(pair.value).unwrap().as_str(), Some("true"));
+        assert!(doc.remove_mapping_pair(root, "debug").is_none());
+        let key = doc.add_scalar(None, "version", ScalarStyle::Plain);
+        let value = doc.add_scalar(None, "2", ScalarStyle::SingleQuoted);
+        doc.insert_mapping_pair(root, 0, key, value);
+
+        let name = doc.get_path_id(&["name"]).unwrap();
+        let mut node = doc.get_node(name).unwrap().clone();
+        node.data = NodeData::Scalar {
+            value: String::from("web"),
+            style: ScalarStyle::Plain,
+        };
+        let old = doc.replace_node(name, node);
+        assert_eq!(old.as_str(), Some("app"));
+
+        let count = doc.nodes.len();
+        let remap = doc.compact();
+        assert_eq!(doc.nodes.len(), count - 3);
+        assert_eq!(remap.len(), doc.nodes.len());
+        assert_eq!(remap[&root], root);
+        assert!(!remap.contains_key(&removed));
+        assert_eq!(doc.get_node(remap[&port]).unwrap().as_str(), Some("8080"));
+
+        let mut output = Vec::new();
+        let mut emitter = Emitter::new();
+        emitter.set_output_string(&mut output);
+        doc.dump(&mut emitter).unwrap();
+        assert_eq!(
+            core::str::from_utf8(&output).unwrap(),
+            "version: '2'\nname: web\nports: [443, 8080]\n"
+        );
+    }
+
+    #[test]
+    fn anchor_names() {
+        fn round_trip(input: &str) -> String {
+            let mut parser = Parser::new();
+            parser.set_input_slice(input.as_bytes());
+            parser.set_allow_anchor_redefinition(true);
+            let doc = Document::load(&mut parser).unwrap();
+            let mut output = Vec::new();
+            let mut emitter = Emitter::new();
+            emitter.set_output_string(&mut output);
+            doc.dump(&mut emitter).unwrap();
+            String::from_utf8(output).unwrap()
+        }
+
+        assert_eq!(
+            round_trip("base: &base [1]\nother: *base\nunused: &unused 2\n"),
+            "base: &base [1]\nother: *base\nunused: 2\n"
+        );
+        // Redefined names are no longer unique, and generated names avoid
+        // the original ones.
+        assert_eq!(
+            round_trip("- &a [1]\n- *a\n- &a [2]\n- *a\n- &id001 [3]\n- *id001\n"),
+            "- &id002 [1]\n- *id002\n- &id003 [2]\n- *id003\n- &id001 [3]\n- *id001\n"
+        );
+    }
+
+    #[test]
+    fn document_events() {
+        let mut parser = Parser::new();
+        parser.set_input_slice(b"a: &x [1]\nb: *x\n");
+        let doc = Document::load(&mut parser).unwrap();
+
+        let events: Vec<_> = doc.events().map(|event| event.data).collect();
+        assert!(matches!(events[0], EventData::DocumentStart { .. }));
+        assert!(matches!(
+            &events[3],
+            EventData::SequenceStart { anchor: Some(anchor), .. } if anchor == "x"
+        ));
+        assert!(matches!(&events[7], EventData::Alias { anchor } if anchor == "x"));
+        assert!(matches!(events[9], EventData::DocumentEnd { .. }));
+        assert_eq!(events.len(), 10);
+        assert_eq!(Document::new(None, &[], true, true).events().count(), 0);
+
+        let mut output = Vec::new();
+        let mut emitter = Emitter::new();
+        emitter.set_output_string(&mut output);
+        doc.dump(&mut emitter).unwrap();
+        doc.dump(&mut emitter).unwrap();
+        emitter.close().unwrap();
+        drop(emitter);
+        assert_eq!(
+            core::str::from_utf8(&output).unwrap(),
+            "a: &x [1]\nb: *x\n---\na: &x [1]\nb: *x\n"
+        );
+    }
+
+    #[test]
+    fn streams() {
+        let mut parser = Parser::new();
+        parser.set_input_slice(b"a\n---\n[b]\n---\n[c\n");
+        let mut documents = parser.documents();
+        assert!(documents.next().unwrap().is_ok());
+        assert!(documents.next().unwrap().is_ok());
+        assert!(documents.next().unwrap().is_err());
+        assert!(documents.next().is_none());
+
+        let mut parser = Parser::new();
+        parser.set_input_slice(b"a\n---\n[b]\n");
+        let mut stream = Stream::load(&mut parser).unwrap();
+        assert_eq!(stream.documents.len(), 2);
+        stream
+            .documents
+            .insert(1, Document::new(None, &[], true, true));
+
+        let mut output = Vec::new();
+        let mut emitter = Emitter::new();
+        emitter.set_output_string(&mut output);
+        stream.dump(&mut emitter).unwrap();
+        drop(emitter);
+        assert_eq!(core::str::from_utf8(&output).unwrap(), "a\n--- [b]\n");
+    }
+
+    #[test]
+    fn semantic_diff() {
+        let a = load("name: app\nports: &p [80, 443]\ncopy: *p\n");
+        let b = load("{copy: [80, 443], \"name\": app, ports: [80, 443]}");
+        assert!(a.semantic_eq(&b));
+        assert!(a.diff(&b).is_empty());
+        assert!(!a.semantic_eq(&load("name: '80'\n")));
+
+        let c = load("name: app\nports: [80, \"443\", 8080]\nextra: 1\n");
+        let differences = a.diff(&c);
+        let summary: Vec<_> = differences
+            .iter()
+            .map(|difference| (difference.kind, difference.path.join("/")))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (DifferenceKind::Changed, String::from("ports/1")),
+                (DifferenceKind::Added, String::from("ports/2")),
+                (DifferenceKind::Removed, String::from("copy")),
+                (DifferenceKind::Added, String::from("extra")),
+            ]
+        );
+        assert_eq!(differences[0].old_mark.line, 1);
+        assert_eq!(differences[0].old_mark.column, 15);
+        assert_eq!(differences[0].new_mark.column, 12);
+        assert_eq!(differences[3].new_mark.line, 2);
+
+        // Each pair is matched to one pair of the other mapping at most.
+        let mut parser = Parser::new();
+        parser.set_input_slice(b"{a: 1, a: 1}");
+        parser.set_schema(Schema::Core);
+        parser.set_strict(false);
+        let duplicated = Document::load(&mut parser).unwrap();
+        let distinct = load("{a: 1, b: 2}");
+        assert!(!duplicated.semantic_eq(&distinct));
+        let summary: Vec<_> = duplicated
+            .diff(&distinct)
+            .iter()
+            .map(|difference| (difference.kind, difference.path.join("/")))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (DifferenceKind::Removed, String::from("a")),
+                (DifferenceKind::Added, String::from("b")),
+            ]
+        );
+
+        // Deeply nested nodes do not overflow the stack.
+        let nested = "- ".repeat(100_000);
+        let deep = load(&format!("{nested}x\n"));
+        assert!(deep.semantic_eq(&deep.clone()));
+        assert!(!deep.semantic_eq(&load(&format!("{nested}y\n"))));
+        let differences = deep.diff(&load(&format!("{nested}[y]\n")));
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].kind, DifferenceKind::Changed);
+        assert_eq!(differences[0].path.len(), 100_000);
+        let deep_key = load(&format!("? {nested}x\n: 1\n"));
+        assert_eq!(deep_key.diff(&load("{}")).len(), 1);
+    }
+
+    #[test]
+    fn merge_documents() {
+        const BASE: &str = "server: {host: localhost, port: 80}\ntags: [a]\nname: base\n";
+        const OVERLAY: &str = "server: {port: 8080, tls: &t true}\ntags: [b]\nname: [*t]\n";
+
+        let mut doc = load(BASE);
+        let overlay = load(OVERLAY);
+        doc.merge_from(&overlay, MergeStrategy::Replace);
+        assert_eq!(
+            dump(&doc),
+            "server: {host: localhost, port: 8080, tls: &t true}\ntags: [b]\nname: [*t]\n"
+        );
+
+        let mut doc = load(BASE);
+        let copied = doc.merge_from(&overlay, MergeStrategy::Append);
+        assert_eq!(
+            dump(&doc),
+            "server: {host: localhost, port: 8080, tls: &t true}\ntags: [a, b]\nname: [*t]\n"
+        );
+        // Copied nodes keep the marks of the overlay.
+        let tls = doc.get_path_id(&["server", "tls"]).unwrap();
+        assert_eq!(
+            copied[&overlay.get_path_id(&["server", "tls"]).unwrap()],
+            tls
+        );
+        assert_eq!(doc.get_node(tls).unwrap().start_mark.column, 26);
+        let host = doc.get_path_id(&["server", "host"]).unwrap();
+        assert!(!copied.values().any(|id| *id == host));
+
+        let mut doc = Document::new(None, &[], true, true);
+        doc.merge_from(&overlay, MergeStrategy::Replace);
+        assert!(doc.semantic_eq(&overlay));
+
+        // Recursive aliases are merged once.
+        let mut doc = load("&a {x: *a}");
+        doc.merge_from(&load("&b {x: *b, y: 1}"), MergeStrategy::Replace);
+        assert_eq!(dump(&doc), "&a {x: *a, y: 1}\n");
+
+        // Only the merged pair gets the new value, not the aliases of the old
+        // value, and the new value keeps the anchor of the old one.
+        let mut doc = load("a: &x 1\nb: *x\n");
+        doc.merge_from(&load("a: 2\n"), MergeStrategy::Replace);
+        assert_eq!(dump(&doc), "a: 2\nb: 1\n");
+        let a = doc.get_path_id(&["a"]).unwrap();
+        assert_eq!(doc.get_node(a).unwrap().anchor.as_deref(), Some("x"));
+        let mut doc = load("a: &x 1\nb: *x\n");
+        doc.merge_from(&load("a: &y 2\nc: *y\n"), MergeStrategy::Replace);
+        assert_eq!(dump(&doc), "a: &y 2\nb: 1\nc: *y\n");
+    }
+
+    #[test]
+    fn visitors() {
+        #[derive(Default)]
+        struct Collect(Vec<String>);
+
+        impl Visitor for Collect {
+            fn visit_scalar(&mut self, path: &[String], _id: NodeId, node: &Node) {
+                let value = node.as_str().unwrap();
+                self.0.push(alloc::format!("{}={value}", path.join("/")));
+            }
+
+            fn enter_mapping(&mut self, path: &[String], _id: NodeId, _node: &Node) {
+                self.0.push(alloc::format!("{}{{", path.join("/")));
+            }
+
+            fn exit_mapping(&mut self, _path: &[String], _id: NodeId, _node: &Node) {
+                self.0.push(String::from("}"));
+            }
+
+            fn visit_cycle(&mut self, path: &[String], _id: NodeId) {
+                self.0.push(alloc::format!("{}=cycle", path.join("/")));
+            }
+        }
+
+        #[derive(Default)]
+        struct Once {
+            scalars: usize,
+            repeats: usize,
+        }
+
+        impl Visitor for Once {
+            fn visit_scalar(&mut self, _path: &[String], _id: NodeId, _node: &Node) {
+                self.scalars += 1;
+            }
+
+            fn visit_repeat(&mut self, _path: &[String], _id: NodeId) -> bool {
+                self.repeats += 1;
+                false
+            }
+        }
+
+        struct Upper;
+
+        impl VisitorMut for Upper {
+            fn visit_scalar(&mut self, _path: &[String], _id: NodeId, node: &mut Node) {
+                if let NodeData::Scalar { value, .. } = &mut node.data {
+                    *value = value.to_uppercase();
+                }
+            }
+        }
+
+        let mut parser = Parser::new();
+        parser.set_input_slice(b"a: {b: x}\nc: &s [y, *s]\n");
+        let mut doc = Document::load(&mut parser).unwrap();
+        doc.walk_mut(&mut Upper);
+        let mut collect = Collect::default();
+        doc.walk(&mut collect);
+        assert_eq!(
+            collect.0,
+            ["{", "a{", "a/b=X", "}", "c/0=Y", "c/1=cycle", "}"]
+        );
+
+        // Aliases of aliases are not expanded if each node is visited once.
+        let mut input = String::from("l0: &l0 [x, x]\n");
+        for n in 1..=40 {
+            writeln!(input, "l{n}: &l{n} [*l{m}, *l{m}]", m = n - 1).unwrap();
+        }
+        let mut parser = Parser::new();
+        parser.set_input_slice(input.as_bytes());
+        let doc = Document::load(&mut parser).unwrap();
+        let mut once = Once::default();
+        doc.walk(&mut once);
+        assert_eq!((once.scalars, once.repeats), (2, 80));
+    }
+
+    #[test]
+    fn canonical_form() {
+        let a = load("b: &x [1, {d: 2, c: 3}]\na: *x\n");
+        let b = load("{\"a\": [1, {c: 3, d: 2}], b: [1, {c: 3, 'd': 2}]}");
+        let canonical = a.canonicalize(None).unwrap();
+        assert!(a.canonicalize(Some(17)).is_ok());
+        let err = a.canonicalize(Some(16)).unwrap_err();
+        assert_eq!(err.problem(), "exceeded the alias expansion limit");
+        assert_eq!(
+            dump(&canonical),
+            "a:\n- 1\n- c: 3\n  d: 2\nb:\n- 1\n- c: 3\n  d: 2\n"
+        );
+        assert_eq!(canonical.nodes.len(), 17);
+
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_eq!(a.fingerprint(), canonical.fingerprint());
+        assert_eq!(a.fingerprint(), 0xccf7_633c_bdc3_41b1);
+        assert_ne!(
+            a.fingerprint(),
+            load("b: [1, {d: 2, c: 3}]\na: [1]\n").fingerprint()
+        );
+        assert_ne!(load("a: 1\n").fingerprint(), load("a: '1'\n").fingerprint());
+        let x = a.get_path_id(&["b"]).unwrap();
+        let y = b.get_path_id(&["a"]).unwrap();
+        assert_eq!(
+            a.get_node(x).unwrap().fingerprint(&a),
+            b.get_node(y).unwrap().fingerprint(&b)
+        );
+
+        let recursive = load("&x [*x]");
+        let err = recursive.canonicalize(None).unwrap_err();
+        assert_eq!(err.problem(), "found recursive alias");
+        assert_ne!(recursive.fingerprint(), load("[[]]").fingerprint());
+
+        // Shared nodes are hashed once, and are not expanded past the limit.
+        let mut input = String::from("- &x0 [a]\n");
+        for n in 1..=40 {
+            writeln!(input, "- &x{n} [*x{m}, *x{m}]", m = n - 1).unwrap();
+        }
+        let shared = load(&input);
+        assert_ne!(shared.fingerprint(), load("[]").fingerprint());
+        let err = shared.canonicalize(Some(1000)).unwrap_err();
+        assert_eq!(err.problem(), "exceeded the alias expansion limit");
+
+        // Deeply nested nodes do not overflow the stack.
+        let nested = "- ".repeat(100_000);
+        let deep = load(&format!("? {nested}y\n: 1\n? {nested}x\n: 2\n"));
+        let canonical = deep.canonicalize(None).unwrap();
+        assert_eq!(deep.fingerprint(), canonical.fingerprint());
+        let NodeData::Mapping { pairs, .. } = &canonical.nodes[0].data else {
+            panic!("expected a mapping");
+        };
+        assert_eq!(
+            canonical.get_node(pairs[0].value).unwrap().as_i64(),
+            Some(2)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize() {
+        use serde::Deserialize as _;
+        use std::collections::BTreeMap;
+
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Config<'a> {
+            name: &'a str,
+            quoted: String,
+            count: u8,
+            hex: i64,
+            ratio: f64,
+            enabled: bool,
+            missing: Option<i32>,
+            tags: Vec<String>,
+            limits: BTreeMap<String, Limits>,
+            mode: Mode,
+        }
+
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Limits {
+            cpu: f64,
+        }
+
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        enum Mode {
+            Fast,
+            Custom { level: u8 },
+        }
+
+        #[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+        #[serde(untagged)]
+        enum Tree {
+            Leaf(u8),
+            List(Vec<Tree>),
+        }
+
+        let input = "name: app\nquoted: 'true'\ncount: 3\nhex: 0x1f\nratio: .inf\nenabled: True\n\
+                     missing: ~\ntags: [a, \"1.0\", null]\n\
+                     limits: {low: &low {cpu: 0.5}, default: *low}\nmode: {Custom: {level: 2}}\n";
+        let config: Config = from_str(input).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "app",
+                quoted: String::from("true"),
+                count: 3,
+                hex: 31,
+                ratio: f64::INFINITY,
+                enabled: true,
+                missing: None,
+                tags: vec![String::from("a"), String::from("1.0"), String::from("null")],
+                limits: [
+                    (String::from("low"), Limits { cpu: 0.5 }),
+                    (String::from("default"), Limits { cpu: 0.5 }),
+                ]
+                .into_iter()
+                .collect(),
+                mode: Mode::Custom { level: 2 },
+            }
+        );
+        assert_eq!(from_str::<Mode>("Fast").unwrap(), Mode::Fast);
+        assert_eq!(from_str::<Option<u8>>("").unwrap(), None);
+
+        let err = from_str::<Limits>("\ncpu: [1]").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Data);
+        assert_eq!(err.problem_mark().map(|mark| mark.line), Some(1));
+        assert!(from_str::<u8>("300").is_err());
+        assert!(from_str::<Vec<u8>>("--- [1]\n--- [2]").is_err());
+
+        let err = from_str::<Vec<Vec<u8>>>("&a [*a]").unwrap_err();
+        assert_eq!(err.problem(), "found recursive alias");
+        let laughs = "a: &a [1, 1, 1, 1, 1, 1, 1, 1, 1, 1]\nb: &b [*a, *a, *a, *a, *a, *a, *a, *a, *a, *a]\n\
+                      c: &c [*b, *b, *b, *b, *b, *b, *b, *b, *b, *b]\nd: [*c, *c, *c, *c, *c, *c, *c, *c, *c, *c]";
+        let mut parser = Parser::new();
+        parser.set_input_slice(laughs.as_bytes());
+        parser.set_limits(ParserLimits {
+            max_alias_expansion: Some(1000),
+            ..ParserLimits::default()
+        });
+        let mut deserializer = Deserializer::new(parser);
+        let err = serde::de::IgnoredAny::deserialize(&mut deserializer).unwrap_err();
+        assert_eq!(err.problem(), "exceeded the alias expansion limit");
+
+        let b = Tree::List(vec![Tree::Leaf(2)]);
+        let a = Tree::List(vec![Tree::Leaf(1), b.clone()]);
+        let c = Tree::List(vec![a.clone(), b.clone()]);
+        assert_eq!(
+            from_str::<Tree>("[&a [1, &b [2]], *b, &c [*a, *b], *c]").unwrap(),
+            Tree::List(vec![a, b, c.clone(), c])
+        );
+
+        // Ignored values count towards the depth limit.
+        let input = alloc::format!("cpu: 1\nz: {}{}", "&a [".repeat(8000), "]".repeat(8000));
+        let err = from_str::<Limits>(&input).unwrap_err();
+        assert_eq!(err.limit(), Some(Limit::Depth));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize() {
+        use serde::Serialize as _;
+        use std::collections::BTreeMap;
+
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct Config {
+            name: String,
+            values: Vec<String>,
+            ratio: f64,
+            small: f32,
+            count: i64,
+            missing: Option<u8>,
+            modes: Vec<Mode>,
+            extra: BTreeMap<String, (u8, bool)>,
+        }
+
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        enum Mode {
+            Fast,
+            Level(u8),
+            Custom { level: u8 },
+        }
+
+        let config = Config {
+            name: String::from("app: 1"),
+            values: [
+                "true",
+                "1.0",
+                "null",
+                "0x1f",
+                "~",
+                "",
+                "plain",
+                "two\nlines",
+            ]
+            .map(String::from)
+            .to_vec(),
+            ratio: 1.0,
+            small: 0.1,
+            count: -3,
+            missing: None,
+            modes: vec![Mode::Fast, Mode::Level(2), Mode::Custom { level: 3 }],
+            extra: [(String::from("x"), (1, false))].into_iter().collect(),
+        };
+        let output = to_string(&config).unwrap();
+        assert!(output.contains("- 'true'\n"));
+        assert!(output.contains("- plain\n"));
+        assert!(output.contains("ratio: 1.0\n"));
+        assert!(output.contains("small: 0.1\n"));
+        assert_eq!(from_str::<Config>(&output).unwrap(), config);
+
+        let mut output = Vec::new();
+        let mut emitter = Emitter::new();
+        emitter.set_output_string(&mut output);
+        let mut serializer = Serializer::new(emitter);
+        serializer.set_scalar_style(ScalarStyle::DoubleQuoted);
+        serializer.set_sequence_style(SequenceStyle::Flow);
+        vec!["a", "null"].serialize(&mut serializer).unwrap();
+        serializer.set_mapping_style(MappingStyle::Flow);
+        [("b", 1.5)]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>()
+            .serialize(&mut serializer)
+            .unwrap();
+        serializer.end().unwrap();
+        drop(serializer);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "[\"a\", \"null\"]\n--- {\"b\": 1.5}\n"
+        );
+    }
+
+    #[test]
+    fn utf32_roundtrip() {
+        for encoding in [Encoding::Utf32Le, Encoding::Utf32Be] {
+            let mut output = Vec::new();
+            let mut emitter = Emitter::new();
+            emitter.set_encoding(encoding);
+            emitter.set_output(&mut output);
+            emitter.set_unicode(true);
+            for event in [
+                Event::stream_start(encoding),
+                Event::document_start(None, &[], true),
+                Event::scalar(
+                    None,
+                    None,
+                    "caf\u{e9} \u{1f600}",
+                    true,
+                    false,
+                    ScalarStyle::Plain,
+                ),
+                Event::document_end(true),
+                Event::stream_end(),
+            ] {
+                emitter.emit(event).unwrap();
+            }
+            drop(emitter);
+            assert_eq!(output.len() % 4, 0);
+
+            let mut parser = Parser::new();
+            let mut read_in = output.as_slice();
+            parser.set_input_string(&mut read_in);
+            let events = parser.collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(events[0].data, EventData::StreamStart { encoding });
+            assert!(matches!(
+                &events[2].data,
+                EventData::Scalar { value, .. } if value == "caf\u{e9} \u{1f600}"
+            ));
+
+            // A truncated final character is a reader error.
+            let mut parser = Parser::new();
+            let mut read_in = &output[..output.len() - 1];
+            parser.set_input_string(&mut read_in);
+            let err = parser.collect::<Result<Vec<_>, _>>().unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Reader);
+            assert_eq!(err.problem(), "incomplete UTF-32 character");
+        }
+    }
+
+    #[test]
+    fn detect_encoding_without_bom() {
+        fn encode(encoding: Encoding, input: &str) -> Vec<u8> {
+            match encoding {
+                Encoding::Utf16Le => input.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+                Encoding::Utf16Be => input.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+                Encoding::Utf32Le => input
+                    .chars()
+                    .flat_map(|ch| u32::from(ch).to_le_bytes())
+                    .collect(),
+                Encoding::Utf32Be => input
+                    .chars()
+                    .flat_map(|ch| u32::from(ch).to_be_bytes())
+                    .collect(),
+                _ => input.as_bytes().to_vec(),
+            }
+        }
+
+        for encoding in [
+            Encoding::Utf8,
+            Encoding::Utf16Le,
+            Encoding::Utf16Be,
+            Encoding::Utf32Le,
+            Encoding::Utf32Be,
+        ] {
+            let input = encode(encoding, "key: v\u{e4}lue\n");
+            let mut parser = Parser::new();
+            let mut read_in = input.as_slice();
+            parser.set_input_string(&mut read_in);
+            let events = parser.collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(events[0].data, EventData::StreamStart { encoding });
+            assert!(matches!(
+                &events[4].data,
+                EventData::Scalar { value, .. } if value == "v\u{e4}lue"
+            ));
+        }
+    }
+
     fn zip_longest<A: Iterator, B: Iterator>(
         a: A,
         b: B,
@@ -1,10 +1,12 @@
+use alloc::borrow::Cow;
 use alloc::string::String;
 use alloc::{vec, vec::Vec};
 
 use crate::scanner::Scanner;
 use crate::{
-    Encoding, Error, Event, EventData, MappingStyle, Mark, Result, ScalarStyle, SequenceStyle,
-    TagDirective, TokenData, VersionDirective,
+    BorrowedEvent, BorrowedToken, Comments, Documents, Encoding, Error, Event, EventData, Limit,
+    MappingStyle, Mark, NodeId, Result, ScalarStyle, Schema, SequenceStyle, TagDirective,
+    TokenData, VersionDirective,
 };
 
 /// The parser structure.
@@ -21,6 +23,18 @@ pub struct Parser<'r> {
     pub(crate) tag_directives: Vec<TagDirective>,
     /// The alias data.
     pub(crate) aliases: Vec<AliasData>,
+    /// The COMMENT tokens not yet attached to an event.
+    pub(crate) pending_comments: Vec<BorrowedToken<'r>>,
+    /// The number of events, anchors and aliases in the current document.
+    pub(crate) document_counts: DocumentCounts,
+    /// The expanded node sizes of the document being loaded.
+    pub(crate) expansion: AliasExpansion,
+    /// The schema that resolves the tags of plain scalars in documents.
+    pub(crate) schema: Schema,
+    /// Are documents with duplicate mapping keys rejected?
+    pub(crate) strict: bool,
+    /// May an anchor be defined again, shadowing the earlier definition?
+    pub(crate) allow_anchor_redefinition: bool,
 }
 
 impl<'r> Default for Parser<'r> {
@@ -29,6 +43,53 @@ impl<'r> Default for Parser<'r> {
     }
 }
 
+/// Resource limits for parsing untrusted input.
+///
+/// Every limit is disabled (`None`) by default. Exceeding a limit is an error
+/// of kind [`ErrorKind::Limit`](crate::ErrorKind::Limit), and
+/// [`Error::limit()`] tells which limit was exceeded.
+///
+/// See [`Parser::set_limits()`] and [`Scanner::set_limits()`].
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub struct ParserLimits {
+    /// The maximum nesting depth of block and flow collections.
+    pub max_depth: Option<usize>,
+    /// The maximum length of a scalar value (in bytes).
+    pub max_scalar_length: Option<usize>,
+    /// The maximum number of events in a document, including the
+    /// DOCUMENT-START and DOCUMENT-END events.
+    pub max_events_per_document: Option<usize>,
+    /// The maximum number of anchors in a document.
+    pub max_anchors: Option<usize>,
+    /// The maximum number of alias references in a document.
+    pub max_aliases: Option<usize>,
+    /// The maximum size of the input (in bytes).
+    pub max_input_bytes: Option<usize>,
+    /// The maximum number of nodes of a document loaded with
+    /// [`Document::load()`](crate::Document::load), counting the nodes of an
+    /// aliased node again for every alias.
+    ///
+    /// Unlike the other limits, exceeding this limit is a composer error. A
+    /// document with a recursive alias exceeds any limit.
+    pub max_alias_expansion: Option<usize>,
+}
+
+impl ParserLimits {
+    /// Is `value` above the limit `max`?
+    pub(crate) fn exceeds(max: Option<usize>, value: usize) -> bool {
+        max.is_some_and(|max| value > max)
+    }
+}
+
+/// The number of events, anchors and aliases seen in the current document.
+#[derive(Copy, Clone, Default)]
+pub(crate) struct DocumentCounts {
+    events: usize,
+    anchors: usize,
+    aliases: usize,
+}
+
 /// This structure holds information about a potential simple key.
 #[derive(Copy, Clone)]
 #[non_exhaustive]
@@ -98,13 +159,24 @@ pub enum ParserState {
     End = 23,
 }
 
+/// The sizes of the nodes of the document being loaded, with every alias
+/// expanded.
+#[derive(Default)]
+pub(crate) struct AliasExpansion {
+    /// The number of nodes loaded so far.
+    pub(crate) total: usize,
+    /// The size of each node, or the total before the node while the node is
+    /// an unfinished collection.
+    pub(crate) sizes: Vec<usize>,
+}
+
 /// This structure holds aliases data.
 #[non_exhaustive]
 pub struct AliasData {
     /// The anchor.
     pub anchor: String,
     /// The node id.
-    pub index: i32,
+    pub index: NodeId,
     /// The anchor mark.
     pub mark: Mark,
 }
@@ -133,6 +205,12 @@ impl<'r> Parser<'r> {
             marks: Vec::with_capacity(16),
             tag_directives: Vec::with_capacity(16),
             aliases: Vec::new(),
+            pending_comments: Vec::new(),
+            document_counts: DocumentCounts::default(),
+            expansion: AliasExpansion::default(),
+            schema: Schema::default(),
+            strict: true,
+            allow_anchor_redefinition: false,
         }
     }
 
@@ -146,6 +224,13 @@ impl<'r> Parser<'r> {
         self.scanner.set_input_string(input);
     }
 
+    /// Set an in-memory input.
+    ///
+    /// See [`Scanner::set_input_slice()`].
+    pub fn set_input_slice(&mut self, input: &'r [u8]) {
+        self.scanner.set_input_slice(input);
+    }
+
     /// Set a generic input handler.
     pub fn set_input(&mut self, input: &'r mut dyn std::io::BufRead) {
         self.scanner.set_input(input);
@@ -156,6 +241,66 @@ impl<'r> Parser<'r> {
         self.scanner.set_encoding(encoding);
     }
 
+    /// Attach comments to the produced events.
+    ///
+    /// Comments on their own lines become the leading comments of the
+    /// following node, or the trailing comments of the preceding scalar if
+    /// they close a collection. Comments on the same line as a node become its
+    /// inline comments. Comments after the end of a flow collection go to its
+    /// end event, and comments after a directive to
+    /// [`Comments::directives`]. Comments that cannot be attached to a node
+    /// end up on the [`EventData::DocumentEnd`] event. Comments outside of any
+    /// document are dropped.
+    ///
+    /// See [`Comments`].
+    pub fn set_preserve_comments(&mut self, preserve: bool) {
+        self.scanner.set_preserve_comments(preserve);
+    }
+
+    /// Set the resource limits.
+    ///
+    /// The limits on the nesting depth, scalar length, and input size are
+    /// enforced by the scanner, the limits on the number of events, anchors
+    /// and aliases by the parser.
+    pub fn set_limits(&mut self, limits: ParserLimits) {
+        self.scanner.set_limits(limits);
+    }
+
+    /// Set the schema that resolves the tags of untagged plain scalars in
+    /// [`Document::load()`](crate::Document::load).
+    ///
+    /// The default is [`Schema::Failsafe`], where every scalar is a string.
+    pub fn set_schema(&mut self, schema: Schema) {
+        self.schema = schema;
+    }
+
+    /// Set if [`Document::load()`](crate::Document::load) rejects mappings
+    /// with duplicate keys.
+    ///
+    /// Strict mode is on by default. Keys are compared by their resolved tags
+    /// and values, including complex keys.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Set if [`Document::load()`](crate::Document::load) allows an anchor to
+    /// be defined more than once in a document.
+    ///
+    /// As in the YAML specification, an alias then refers to the most recent
+    /// definition of the anchor. By default, a redefined anchor is an error.
+    pub fn set_allow_anchor_redefinition(&mut self, allow: bool) {
+        self.allow_anchor_redefinition = allow;
+    }
+
+    /// Load the documents of the input stream one at a time.
+    ///
+    /// The iterator ends after the last document, or after the first error.
+    ///
+    /// See [`Document::load()`](crate::Document::load).
+    pub fn documents(&mut self) -> Documents<'_, 'r> {
+        Documents::new(self)
+    }
+
     /// Parse the input stream and produce the next parsing event.
     ///
     /// Call the function subsequently to produce a sequence of events
@@ -168,13 +313,61 @@ impl<'r> Parser<'r> {
     /// the calls of [`Document::load()`](crate::Document::load). Doing this
     /// will break the parser.
     pub fn parse(&mut self) -> Result<Event> {
+        self.parse_borrowed().map(BorrowedEvent::into_owned)
+    }
+
+    /// Like [`Parser::parse()`], but borrow the anchors, tags and scalar
+    /// values of the event from the input where possible.
+    ///
+    /// Values are only borrowed when the input was set with
+    /// [`Parser::set_input_slice()`] and they appear verbatim in it, i.e. they
+    /// need no unescaping, line folding or tag handle resolution. All other
+    /// values are owned.
+    pub fn parse_borrowed(&mut self) -> Result<BorrowedEvent<'r>> {
         if self.scanner.stream_end_produced || self.state == ParserState::End {
-            return Ok(Event::stream_end());
+            return Ok(Event {
+                data: EventData::StreamEnd,
+                start_mark: Mark::default(),
+                end_mark: Mark::default(),
+            });
         }
-        self.state_machine()
+        let event = self.state_machine()?;
+        self.count_event(&event)?;
+        Ok(event)
     }
 
-    fn state_machine(&mut self) -> Result<Event> {
+    /// Enforce the limits on the number of events, anchors and aliases.
+    fn count_event(&mut self, event: &BorrowedEvent<'r>) -> Result<()> {
+        let limits = &self.scanner.limits;
+        let counts = &mut self.document_counts;
+        let exceeded = match &event.data {
+            EventData::DocumentStart { .. } => {
+                *counts = DocumentCounts::default();
+                None
+            }
+            EventData::Alias { .. } => {
+                counts.aliases += 1;
+                ParserLimits::exceeds(limits.max_aliases, counts.aliases).then_some(Limit::Aliases)
+            }
+            EventData::Scalar { anchor, .. }
+            | EventData::SequenceStart { anchor, .. }
+            | EventData::MappingStart { anchor, .. } => {
+                counts.anchors += usize::from(anchor.is_some());
+                ParserLimits::exceeds(limits.max_anchors, counts.anchors).then_some(Limit::Anchors)
+            }
+            _ => None,
+        };
+        counts.events += 1;
+        if let Some(limit) = exceeded.or_else(|| {
+            ParserLimits::exceeds(limits.max_events_per_document, counts.events)
+                .then_some(Limit::Events)
+        }) {
+            return Err(Error::limit_exceeded(limit, event.start_mark));
+        }
+        Ok(())
+    }
+
+    fn state_machine(&mut self) -> Result<BorrowedEvent<'r>> {
         match self.state {
             ParserState::StreamStart => self.parse_stream_start(),
             ParserState::ImplicitDocumentStart => self.parse_document_start(true),
@@ -209,8 +402,8 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_stream_start(&mut self) -> Result<Event> {
-        let token = self.scanner.peek()?;
+    fn parse_stream_start(&mut self) -> Result<BorrowedEvent<'r>> {
+        let token = self.peek_token()?;
 
         if let TokenData::StreamStart { encoding } = &token.data {
             let event = Event {
@@ -234,15 +427,15 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_document_start(&mut self, implicit: bool) -> Result<Event> {
+    fn parse_document_start(&mut self, implicit: bool) -> Result<BorrowedEvent<'r>> {
         let mut version_directive: Option<VersionDirective> = None;
 
         let mut tag_directives = vec![];
-        let mut token = self.scanner.peek()?;
+        let mut token = self.peek_token()?;
         if !implicit {
             while let TokenData::DocumentEnd = &token.data {
                 self.scanner.skip_token();
-                token = self.scanner.peek()?;
+                token = self.peek_token()?;
             }
         }
         if implicit
@@ -254,31 +447,38 @@ impl<'r> Parser<'r> {
                     | TokenData::StreamEnd
             )
         {
+            let start_mark = token.start_mark;
+            let end_mark = token.end_mark;
             let event = Event {
                 data: EventData::DocumentStart {
                     version_directive: None,
                     tag_directives: vec![],
                     implicit: true,
+                    comments: self.take_comments(),
                 },
-                start_mark: token.start_mark,
-                end_mark: token.end_mark,
+                start_mark,
+                end_mark,
             };
-            self.process_directives(None, None)?;
+            _ = self.process_directives(None, None)?;
             self.states.push(ParserState::DocumentEnd);
             self.state = ParserState::BlockNode;
             Ok(event)
         } else if !matches!(token.data, TokenData::StreamEnd) {
             let end_mark: Mark;
             let start_mark: Mark = token.start_mark;
-            self.process_directives(Some(&mut version_directive), Some(&mut tag_directives))?;
-            token = self.scanner.peek()?;
+            let directive_comments =
+                self.process_directives(Some(&mut version_directive), Some(&mut tag_directives))?;
+            token = self.peek_token()?;
             if let TokenData::DocumentStart = token.data {
                 end_mark = token.end_mark;
-                let event = Event {
+                let mut comments = self.take_comments();
+                comments.directives = directive_comments;
+                let mut event = Event {
                     data: EventData::DocumentStart {
                         version_directive,
                         tag_directives: core::mem::take(&mut tag_directives),
                         implicit: false,
+                        comments,
                     },
                     start_mark,
                     end_mark,
@@ -286,6 +486,9 @@ impl<'r> Parser<'r> {
                 self.states.push(ParserState::DocumentEnd);
                 self.state = ParserState::DocumentContent;
                 self.scanner.skip_token();
+                if self.scanner.preserve_comments {
+                    self.take_inline_comment(&mut event)?;
+                }
                 Ok(event)
             } else {
                 Err(Error::parser(
@@ -307,8 +510,8 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_document_content(&mut self) -> Result<Event> {
-        let token = self.scanner.peek()?;
+    fn parse_document_content(&mut self) -> Result<BorrowedEvent<'r>> {
+        let token = self.peek_token()?;
         if let TokenData::VersionDirective { .. }
         | TokenData::TagDirective { .. }
         | TokenData::DocumentStart
@@ -323,10 +526,10 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_document_end(&mut self) -> Result<Event> {
+    fn parse_document_end(&mut self) -> Result<BorrowedEvent<'r>> {
         let mut end_mark: Mark;
         let mut implicit = true;
-        let token = self.scanner.peek()?;
+        let token = self.peek_token()?;
         end_mark = token.start_mark;
         let start_mark: Mark = end_mark;
         if let TokenData::DocumentEnd = &token.data {
@@ -336,18 +539,37 @@ impl<'r> Parser<'r> {
         }
         self.tag_directives.clear();
         self.state = ParserState::DocumentStart;
-        Ok(Event {
-            data: EventData::DocumentEnd { implicit },
+        let mut event = Event {
+            data: EventData::DocumentEnd {
+                implicit,
+                comments: self.take_comments(),
+            },
             start_mark,
             end_mark,
-        })
+        };
+        if !implicit && self.scanner.preserve_comments {
+            self.take_inline_comment(&mut event)?;
+        }
+        Ok(event)
+    }
+
+    fn parse_node(&mut self, block: bool, indentless_sequence: bool) -> Result<BorrowedEvent<'r>> {
+        let mut event = self.parse_node_data(block, indentless_sequence)?;
+        if self.scanner.preserve_comments {
+            self.attach_comments(&mut event)?;
+        }
+        Ok(event)
     }
 
-    fn parse_node(&mut self, block: bool, indentless_sequence: bool) -> Result<Event> {
-        let mut anchor: Option<String> = None;
-        let mut tag_handle: Option<String> = None;
-        let mut tag_suffix: Option<String> = None;
-        let mut tag: Option<String> = None;
+    fn parse_node_data(
+        &mut self,
+        block: bool,
+        indentless_sequence: bool,
+    ) -> Result<BorrowedEvent<'r>> {
+        let mut anchor: Option<Cow<'r, str>> = None;
+        let mut tag_handle: Option<Cow<'r, str>> = None;
+        let mut tag_suffix: Option<Cow<'r, str>> = None;
+        let mut tag: Option<Cow<'r, str>> = None;
         let mut start_mark: Mark;
         let mut end_mark: Mark;
         let mut tag_mark = Mark {
@@ -356,7 +578,7 @@ impl<'r> Parser<'r> {
             column: 0,
         };
 
-        let mut token = self.scanner.peek_mut()?;
+        let mut token = self.peek_token_mut()?;
 
         if let TokenData::Alias { value } = &mut token.data {
             let event = Event {
@@ -378,7 +600,7 @@ impl<'r> Parser<'r> {
             start_mark = token.start_mark;
             end_mark = token.end_mark;
             self.scanner.skip_token();
-            token = self.scanner.peek_mut()?;
+            token = self.peek_token_mut()?;
             if let TokenData::Tag { handle, suffix } = &mut token.data {
                 tag_handle = Some(core::mem::take(handle));
                 tag_suffix = Some(core::mem::take(suffix));
@@ -393,7 +615,7 @@ impl<'r> Parser<'r> {
             start_mark = tag_mark;
             end_mark = token.end_mark;
             self.scanner.skip_token();
-            token = self.scanner.peek_mut()?;
+            token = self.peek_token_mut()?;
             if let TokenData::Anchor { value } = &mut token.data {
                 anchor = Some(core::mem::take(value));
                 end_mark = token.end_mark;
@@ -408,7 +630,11 @@ impl<'r> Parser<'r> {
                 for tag_directive in &self.tag_directives {
                     if tag_directive.handle == *tag_handle_value {
                         let suffix = tag_suffix.as_deref().unwrap_or("");
-                        tag = Some(alloc::format!("{}{}", tag_directive.prefix, suffix));
+                        tag = Some(Cow::Owned(alloc::format!(
+                            "{}{}",
+                            tag_directive.prefix,
+                            suffix
+                        )));
                         break;
                     }
                 }
@@ -423,7 +649,7 @@ impl<'r> Parser<'r> {
             }
         }
 
-        let token = self.scanner.peek_mut()?;
+        let token = self.peek_token_mut()?;
 
         let implicit = tag.is_none() || tag.as_deref() == Some("");
 
@@ -436,6 +662,7 @@ impl<'r> Parser<'r> {
                     tag,
                     implicit,
                     style: SequenceStyle::Block,
+                    comments: Comments::default(),
                 },
                 start_mark,
                 end_mark,
@@ -458,6 +685,7 @@ impl<'r> Parser<'r> {
                     plain_implicit,
                     quoted_implicit,
                     style: *style,
+                    comments: Comments::default(),
                 },
                 start_mark,
                 end_mark,
@@ -474,6 +702,7 @@ impl<'r> Parser<'r> {
                     tag,
                     implicit,
                     style: SequenceStyle::Flow,
+                    comments: Comments::default(),
                 },
                 start_mark,
                 end_mark,
@@ -488,6 +717,7 @@ impl<'r> Parser<'r> {
                     tag,
                     implicit,
                     style: MappingStyle::Flow,
+                    comments: Comments::default(),
                 },
                 start_mark,
                 end_mark,
@@ -502,6 +732,7 @@ impl<'r> Parser<'r> {
                     tag,
                     implicit,
                     style: SequenceStyle::Block,
+                    comments: Comments::default(),
                 },
                 start_mark,
                 end_mark,
@@ -516,6 +747,7 @@ impl<'r> Parser<'r> {
                     tag,
                     implicit,
                     style: MappingStyle::Block,
+                    comments: Comments::default(),
                 },
                 start_mark,
                 end_mark,
@@ -527,10 +759,11 @@ impl<'r> Parser<'r> {
                 data: EventData::Scalar {
                     anchor,
                     tag,
-                    value: String::new(),
+                    value: Cow::Borrowed(""),
                     plain_implicit: implicit,
                     quoted_implicit: false,
                     style: ScalarStyle::Plain,
+                    comments: Comments::default(),
                 },
                 start_mark,
                 end_mark,
@@ -550,20 +783,20 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_block_sequence_entry(&mut self, first: bool) -> Result<Event> {
+    fn parse_block_sequence_entry(&mut self, first: bool) -> Result<BorrowedEvent<'r>> {
         if first {
-            let token = self.scanner.peek()?;
+            let token = self.peek_token()?;
             let mark = token.start_mark;
             self.marks.push(mark);
             self.scanner.skip_token();
         }
 
-        let mut token = self.scanner.peek()?;
+        let mut token = self.peek_token()?;
 
         if let TokenData::BlockEntry = &token.data {
             let mark: Mark = token.end_mark;
             self.scanner.skip_token();
-            token = self.scanner.peek()?;
+            token = self.peek_token()?;
             if matches!(token.data, TokenData::BlockEntry | TokenData::BlockEnd) {
                 self.state = ParserState::BlockSequenceEntry;
                 Self::process_empty_scalar(mark)
@@ -573,7 +806,9 @@ impl<'r> Parser<'r> {
             }
         } else if let TokenData::BlockEnd = token.data {
             let event = Event {
-                data: EventData::SequenceEnd,
+                data: EventData::SequenceEnd {
+                    comments: Comments::default(),
+                },
                 start_mark: token.start_mark,
                 end_mark: token.end_mark,
             };
@@ -593,12 +828,12 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_indentless_sequence_entry(&mut self) -> Result<Event> {
-        let mut token = self.scanner.peek()?;
+    fn parse_indentless_sequence_entry(&mut self) -> Result<BorrowedEvent<'r>> {
+        let mut token = self.peek_token()?;
         if let TokenData::BlockEntry = token.data {
             let mark: Mark = token.end_mark;
             self.scanner.skip_token();
-            token = self.scanner.peek()?;
+            token = self.peek_token()?;
 
             if matches!(
                 token.data,
@@ -612,7 +847,9 @@ impl<'r> Parser<'r> {
             }
         } else {
             let event = Event {
-                data: EventData::SequenceEnd,
+                data: EventData::SequenceEnd {
+                    comments: Comments::default(),
+                },
                 start_mark: token.start_mark,
                 end_mark: token.end_mark,
             };
@@ -621,19 +858,19 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_block_mapping_key(&mut self, first: bool) -> Result<Event> {
+    fn parse_block_mapping_key(&mut self, first: bool) -> Result<BorrowedEvent<'r>> {
         if first {
-            let token = self.scanner.peek()?;
+            let token = self.peek_token()?;
             let mark = token.start_mark;
             self.marks.push(mark);
             self.scanner.skip_token();
         }
 
-        let mut token = self.scanner.peek()?;
+        let mut token = self.peek_token()?;
         if let TokenData::Key = token.data {
             let mark: Mark = token.end_mark;
             self.scanner.skip_token();
-            token = self.scanner.peek()?;
+            token = self.peek_token()?;
             if matches!(
                 token.data,
                 TokenData::Key | TokenData::Value | TokenData::BlockEnd
@@ -646,7 +883,9 @@ impl<'r> Parser<'r> {
             }
         } else if let TokenData::BlockEnd = token.data {
             let event = Event {
-                data: EventData::MappingEnd,
+                data: EventData::MappingEnd {
+                    comments: Comments::default(),
+                },
                 start_mark: token.start_mark,
                 end_mark: token.end_mark,
             };
@@ -666,12 +905,12 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_block_mapping_value(&mut self) -> Result<Event> {
-        let mut token = self.scanner.peek()?;
+    fn parse_block_mapping_value(&mut self) -> Result<BorrowedEvent<'r>> {
+        let mut token = self.peek_token()?;
         if let TokenData::Value = token.data {
             let mark: Mark = token.end_mark;
             self.scanner.skip_token();
-            token = self.scanner.peek()?;
+            token = self.peek_token()?;
             if matches!(
                 token.data,
                 TokenData::Key | TokenData::Value | TokenData::BlockEnd
@@ -689,20 +928,20 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_flow_sequence_entry(&mut self, first: bool) -> Result<Event> {
+    fn parse_flow_sequence_entry(&mut self, first: bool) -> Result<BorrowedEvent<'r>> {
         if first {
-            let token = self.scanner.peek()?;
+            let token = self.peek_token()?;
             let mark = token.start_mark;
             self.marks.push(mark);
             self.scanner.skip_token();
         }
 
-        let mut token = self.scanner.peek()?;
+        let mut token = self.peek_token()?;
         if !matches!(token.data, TokenData::FlowSequenceEnd) {
             if !first {
                 if let TokenData::FlowEntry = token.data {
                     self.scanner.skip_token();
-                    token = self.scanner.peek()?;
+                    token = self.peek_token()?;
                 } else {
                     let token_mark = token.start_mark;
                     let mark = self.marks.pop().unwrap();
@@ -721,6 +960,7 @@ impl<'r> Parser<'r> {
                         tag: None,
                         implicit: true,
                         style: MappingStyle::Flow,
+                        comments: Comments::default(),
                     },
                     start_mark: token.start_mark,
                     end_mark: token.end_mark,
@@ -733,19 +973,24 @@ impl<'r> Parser<'r> {
                 return self.parse_node(false, false);
             }
         }
-        let event = Event {
-            data: EventData::SequenceEnd,
+        let mut event = Event {
+            data: EventData::SequenceEnd {
+                comments: Comments::default(),
+            },
             start_mark: token.start_mark,
             end_mark: token.end_mark,
         };
         self.state = self.states.pop().unwrap();
         _ = self.marks.pop();
         self.scanner.skip_token();
+        if self.scanner.preserve_comments {
+            self.take_inline_comment(&mut event)?;
+        }
         Ok(event)
     }
 
-    fn parse_flow_sequence_entry_mapping_key(&mut self) -> Result<Event> {
-        let token = self.scanner.peek()?;
+    fn parse_flow_sequence_entry_mapping_key(&mut self) -> Result<BorrowedEvent<'r>> {
+        let token = self.peek_token()?;
         if matches!(
             token.data,
             TokenData::Value | TokenData::FlowEntry | TokenData::FlowSequenceEnd
@@ -760,11 +1005,11 @@ impl<'r> Parser<'r> {
         }
     }
 
-    fn parse_flow_sequence_entry_mapping_value(&mut self) -> Result<Event> {
-        let mut token = self.scanner.peek()?;
+    fn parse_flow_sequence_entry_mapping_value(&mut self) -> Result<BorrowedEvent<'r>> {
+        let mut token = self.peek_token()?;
         if let TokenData::Value = token.data {
             self.scanner.skip_token();
-            token = self.scanner.peek()?;
+            token = self.peek_token()?;
             if !matches!(
                 token.data,
                 TokenData::FlowEntry | TokenData::FlowSequenceEnd
@@ -778,32 +1023,34 @@ impl<'r> Parser<'r> {
         Self::process_empty_scalar(mark)
     }
 
-    fn parse_flow_sequence_entry_mapping_end(&mut self) -> Result<Event> {
-        let token = self.scanner.peek()?;
+    fn parse_flow_sequence_entry_mapping_end(&mut self) -> Result<BorrowedEvent<'r>> {
+        let token = self.peek_token()?;
         let start_mark = token.start_mark;
         let end_mark = token.end_mark;
         self.state = ParserState::FlowSequenceEntry;
         Ok(Event {
-            data: EventData::MappingEnd,
+            data: EventData::MappingEnd {
+                comments: Comments::default(),
+            },
             start_mark,
             end_mark,
         })
     }
 
-    fn parse_flow_mapping_key(&mut self, first: bool) -> Result<Event> {
+    fn parse_flow_mapping_key(&mut self, first: bool) -> Result<BorrowedEvent<'r>> {
         if first {
-            let token = self.scanner.peek()?;
+            let token = self.peek_token()?;
             let mark = token.start_mark;
             self.marks.push(mark);
             self.scanner.skip_token();
         }
 
-        let mut token = self.scanner.peek()?;
+        let mut token = self.peek_token()?;
         if !matches!(token.data, TokenData::FlowMappingEnd) {
             if !first {
                 if let TokenData::FlowEntry = token.data {
                     self.scanner.skip_token();
-                    token = self.scanner.peek()?;
+                    token = self.peek_token()?;
                 } else {
                     let token_mark = token.start_mark;
                     let mark = self.marks.pop().unwrap();
@@ -817,7 +1064,7 @@ impl<'r> Parser<'r> {
             }
             if let TokenData::Key = token.data {
                 self.scanner.skip_token();
-                token = self.scanner.peek()?;
+                token = self.peek_token()?;
                 if !matches!(
                     token.data,
                     TokenData::Value | TokenData::FlowEntry | TokenData::FlowMappingEnd
@@ -833,19 +1080,24 @@ impl<'r> Parser<'r> {
                 return self.parse_node(false, false);
             }
         }
-        let event = Event {
-            data: EventData::MappingEnd,
+        let mut event = Event {
+            data: EventData::MappingEnd {
+                comments: Comments::default(),
+            },
             start_mark: token.start_mark,
             end_mark: token.end_mark,
         };
         self.state = self.states.pop().unwrap();
         _ = self.marks.pop();
         self.scanner.skip_token();
+        if self.scanner.preserve_comments {
+            self.take_inline_comment(&mut event)?;
+        }
         Ok(event)
     }
 
-    fn parse_flow_mapping_value(&mut self, empty: bool) -> Result<Event> {
-        let mut token = self.scanner.peek()?;
+    fn parse_flow_mapping_value(&mut self, empty: bool) -> Result<BorrowedEvent<'r>> {
+        let mut token = self.peek_token()?;
         if empty {
             let mark = token.start_mark;
             self.state = ParserState::FlowMappingKey;
@@ -853,7 +1105,7 @@ impl<'r> Parser<'r> {
         }
         if let TokenData::Value = token.data {
             self.scanner.skip_token();
-            token = self.scanner.peek()?;
+            token = self.peek_token()?;
             if !matches!(token.data, TokenData::FlowEntry | TokenData::FlowMappingEnd) {
                 self.states.push(ParserState::FlowMappingKey);
                 return self.parse_node(false, false);
@@ -864,26 +1116,136 @@ impl<'r> Parser<'r> {
         Self::process_empty_scalar(mark)
     }
 
-    fn process_empty_scalar(mark: Mark) -> Result<Event> {
+    fn process_empty_scalar(mark: Mark) -> Result<BorrowedEvent<'r>> {
         Ok(Event {
             data: EventData::Scalar {
                 anchor: None,
                 tag: None,
-                value: String::new(),
+                value: Cow::Borrowed(""),
                 plain_implicit: true,
                 quoted_implicit: false,
                 style: ScalarStyle::Plain,
+                comments: Comments::default(),
             },
             start_mark: mark,
             end_mark: mark,
         })
     }
 
+    /// Peek the next token, collecting any COMMENT tokens on the way.
+    fn peek_token(&mut self) -> Result<&BorrowedToken<'r>> {
+        self.collect_comments()?;
+        self.scanner.peek()
+    }
+
+    /// Like [`Parser::peek_token()`], but return a mutable token.
+    fn peek_token_mut(&mut self) -> Result<&mut BorrowedToken<'r>> {
+        self.collect_comments()?;
+        self.scanner.peek_mut()
+    }
+
+    fn collect_comments(&mut self) -> Result<()> {
+        while let TokenData::Comment { .. } = self.scanner.peek()?.data {
+            let token = self.scanner.take_token();
+            self.pending_comments.push(token);
+        }
+        Ok(())
+    }
+
+    /// Move the pending comments into the leading and inline comment lists.
+    fn take_comments(&mut self) -> Comments {
+        let mut comments = Comments::default();
+        for token in self.pending_comments.drain(..) {
+            if let TokenData::Comment { value, inline } = token.data {
+                if inline {
+                    comments.inline.push(value.into_owned());
+                } else {
+                    comments.leading.push(value.into_owned());
+                }
+            }
+        }
+        comments
+    }
+
+    /// Attach the comment following the last token on its line to the event.
+    fn take_inline_comment(&mut self, event: &mut BorrowedEvent<'r>) -> Result<()> {
+        if let Some(value) = self.take_inline_comment_value()? {
+            if let Some(comments) = event.comments_mut() {
+                comments.inline.push(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Take the comment following the last token on its line, if any.
+    fn take_inline_comment_value(&mut self) -> Result<Option<String>> {
+        let token = self.scanner.peek_mut()?;
+        let TokenData::Comment {
+            value,
+            inline: true,
+        } = &mut token.data
+        else {
+            return Ok(None);
+        };
+        let value = core::mem::take(value).into_owned();
+        self.scanner.skip_token();
+        Ok(Some(value))
+    }
+
+    /// Attach the pending comments to a node event.
+    ///
+    /// A scalar also receives the comment on the rest of its line and, if it
+    /// is the last node of a collection or document, the following comments
+    /// that are indented at least as deep as that collection.
+    fn attach_comments(&mut self, event: &mut BorrowedEvent<'r>) -> Result<()> {
+        let Some(comments) = event.comments_mut() else {
+            return Ok(());
+        };
+        let pending = self.take_comments();
+        comments.leading = pending.leading;
+        comments.inline = pending.inline;
+        if !matches!(event.data, EventData::Scalar { .. }) {
+            return Ok(());
+        }
+
+        self.take_inline_comment(event)?;
+
+        let token = self.peek_token()?;
+        let closes_document = matches!(
+            token.data,
+            TokenData::VersionDirective { .. }
+                | TokenData::TagDirective { .. }
+                | TokenData::DocumentStart
+                | TokenData::DocumentEnd
+                | TokenData::StreamEnd
+        );
+        let column = match token.data {
+            TokenData::BlockEnd => self.marks.last().map_or(0, |mark| mark.column),
+            TokenData::FlowSequenceEnd | TokenData::FlowMappingEnd => 0,
+            _ if closes_document => 0,
+            _ => return Ok(()),
+        };
+        let count = self
+            .pending_comments
+            .iter()
+            .take_while(|token| token.start_mark.column >= column)
+            .count();
+        if let Some(comments) = event.comments_mut() {
+            for token in self.pending_comments.drain(..count) {
+                if let TokenData::Comment { value, .. } = token.data {
+                    comments.trailing.push(value.into_owned());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the comments of the directives, see [`Comments::directives`].
     fn process_directives(
         &mut self,
         version_directive_ref: Option<&mut Option<VersionDirective>>,
         tag_directives_ref: Option<&mut Vec<TagDirective>>,
-    ) -> Result<()> {
+    ) -> Result<Vec<Option<String>>> {
         let default_tag_directives: [TagDirective; 2] = [
             // TODO: Get rid of these heap allocations.
             TagDirective {
@@ -899,7 +1261,8 @@ impl<'r> Parser<'r> {
 
         let mut tag_directives = Vec::with_capacity(16);
 
-        let mut token = self.scanner.peek_mut()?;
+        let mut comments = Vec::new();
+        let mut token = self.peek_token_mut()?;
 
         loop {
             if !matches!(
@@ -908,6 +1271,7 @@ impl<'r> Parser<'r> {
             ) {
                 break;
             }
+            let version = matches!(token.data, TokenData::VersionDirective { .. });
 
             if let TokenData::VersionDirective { major, minor } = &token.data {
                 let mark = token.start_mark;
@@ -932,8 +1296,8 @@ impl<'r> Parser<'r> {
                 });
             } else if let TokenData::TagDirective { handle, prefix } = &mut token.data {
                 let value = TagDirective {
-                    handle: core::mem::take(handle),
-                    prefix: core::mem::take(prefix),
+                    handle: core::mem::take(handle).into_owned(),
+                    prefix: core::mem::take(prefix).into_owned(),
                 };
                 let mark = token.start_mark;
                 self.append_tag_directive(value.clone(), false, mark)?;
@@ -942,7 +1306,18 @@ impl<'r> Parser<'r> {
             }
 
             self.scanner.skip_token();
-            token = self.scanner.peek_mut()?;
+            if self.scanner.preserve_comments {
+                let comment = self.take_inline_comment_value()?;
+                if version {
+                    comments.insert(0, comment);
+                } else {
+                    comments.push(comment);
+                }
+            }
+            token = self.peek_token_mut()?;
+        }
+        if comments.iter().all(Option::is_none) {
+            comments.clear();
         }
 
         let start_mark = token.start_mark;
@@ -964,7 +1339,7 @@ impl<'r> Parser<'r> {
             tag_directives.clear();
         }
 
-        Ok(())
+        Ok(comments)
     }
 
     fn append_tag_directive(
@@ -992,5 +1367,6 @@ impl<'r> Parser<'r> {
 
     pub(crate) fn delete_aliases(&mut self) {
         self.aliases.clear();
+        self.expansion = AliasExpansion::default();
     }
 }
@@ -1,8 +1,126 @@
 use std::io::BufRead;
 
-use alloc::collections::VecDeque;
+use alloc::borrow::Cow;
+use alloc::string::String;
 
-use crate::{scanner::Scanner, Encoding, Error, Result};
+use crate::{scanner::Scanner, Encoding, Error, Limit, ParserLimits, Result, INPUT_BUFFER_SIZE};
+
+/// The source of the input bytes.
+pub(crate) enum Input<'r> {
+    /// A generic reader.
+    Reader(&'r mut dyn BufRead),
+    /// An in-memory input that cannot be scanned in place.
+    Bytes(&'r [u8]),
+}
+
+/// The characters that the scanner has not consumed yet.
+pub(crate) struct InputBuffer<'r> {
+    /// Either an entire in-memory input that is known to be valid UTF-8
+    /// without disallowed characters, so it can be scanned in place, or the
+    /// characters decoded from the input so far.
+    text: Cow<'r, str>,
+    /// The byte position of the first character that has not been consumed.
+    pos: usize,
+}
+
+impl<'r> InputBuffer<'r> {
+    pub(crate) fn new() -> Self {
+        Self {
+            text: Cow::Owned(String::with_capacity(INPUT_BUFFER_SIZE)),
+            pos: 0,
+        }
+    }
+
+    /// Scan an in-memory input in place.
+    pub(crate) fn from_str(input: &'r str) -> Self {
+        Self {
+            text: Cow::Borrowed(input),
+            pos: 0,
+        }
+    }
+
+    /// The in-memory input and the byte position in it, if it is scanned in
+    /// place.
+    pub(crate) fn borrowed(&self) -> Option<(&'r str, usize)> {
+        match self.text {
+            Cow::Borrowed(input) => Some((input, self.pos)),
+            Cow::Owned(_) => None,
+        }
+    }
+
+    /// The characters that have not been consumed yet.
+    #[inline]
+    pub(crate) fn as_str(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    /// Are at least `length` characters available?
+    #[inline]
+    pub(crate) fn is_cached(&self, length: usize) -> bool {
+        has_chars(self.as_str(), length)
+    }
+
+    /// Get the character `index` positions ahead.
+    #[inline]
+    pub(crate) fn get(&self, index: usize) -> Option<char> {
+        let bytes = &self.text.as_bytes()[self.pos..];
+        match bytes.get(..=index) {
+            Some(ahead) if ahead.iter().all(u8::is_ascii) => Some(char::from(ahead[index])),
+            _ => self.get_slow(index),
+        }
+    }
+
+    #[cold]
+    fn get_slow(&self, index: usize) -> Option<char> {
+        self.as_str().chars().nth(index)
+    }
+
+    /// Consume the next character.
+    #[inline]
+    pub(crate) fn pop_front(&mut self) -> Option<char> {
+        let ch = match self.text.as_bytes().get(self.pos) {
+            Some(&byte) if byte.is_ascii() => char::from(byte),
+            Some(_) => self.get_slow(0)?,
+            None => return None,
+        };
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    /// Consume the next `count` characters.
+    pub(crate) fn skip(&mut self, count: usize) {
+        for _ in 0..count {
+            _ = self.pop_front();
+        }
+    }
+
+    /// Consume the longest run of ASCII characters for which `predicate`
+    /// holds, and return it.
+    pub(crate) fn take_ascii_while(&mut self, predicate: impl Fn(u8) -> bool) -> &str {
+        let start = self.pos;
+        let len = self.text.as_bytes()[start..]
+            .iter()
+            .position(|&byte| !byte.is_ascii() || !predicate(byte))
+            .unwrap_or(self.text.len() - start);
+        self.pos += len;
+        &self.text[start..start + len]
+    }
+
+    /// Get the buffer to decode more characters into, or `None` for an
+    /// in-memory input.
+    fn decoded_mut(&mut self) -> Option<&mut String> {
+        let Cow::Owned(decoded) = &mut self.text else {
+            return None;
+        };
+        decoded.drain(..self.pos);
+        self.pos = 0;
+        Some(decoded)
+    }
+}
+
+fn has_chars(text: &str, length: usize) -> bool {
+    text.len() >= length * 4 || text.chars().take(length).count() == length
+}
 
 const BOM_UTF8: [u8; 3] = [0xef, 0xbb, 0xbf];
 const BOM_UTF16LE: [u8; 2] = [0xff, 0xfe];
@@ -14,6 +132,29 @@ fn yaml_parser_determine_encoding(reader: &mut dyn BufRead) -> Result<Option<Enc
         return Ok(None);
     }
 
+    // The encoding detection table of the YAML specification: a BOM, or the
+    // null bytes of an ASCII character in the first code unit. Note that only
+    // the bytes that the reader has already buffered are considered.
+    let (encoding, bom_len) = match initial_bytes {
+        [0x00, 0x00, 0xfe, 0xff, ..] => (Encoding::Utf32Be, 4),
+        [0x00, 0x00, 0x00, _, ..] => (Encoding::Utf32Be, 0),
+        [0xff, 0xfe, 0x00, 0x00, ..] => (Encoding::Utf32Le, 4),
+        [_, 0x00, 0x00, 0x00, ..] => (Encoding::Utf32Le, 0),
+        [0xfe, 0xff, ..] => (Encoding::Utf16Be, 2),
+        [0x00, _, ..] => (Encoding::Utf16Be, 0),
+        [0xff, 0xfe, ..] => (Encoding::Utf16Le, 2),
+        [_, 0x00, ..] => (Encoding::Utf16Le, 0),
+        [0xef, 0xbb, 0xbf, ..] => (Encoding::Utf8, 3),
+        [0xef | 0xfe | 0xff, ..] => return yaml_parser_read_bom(reader),
+        _ => (Encoding::Utf8, 0),
+    };
+    reader.consume(bom_len);
+    Ok(Some(encoding))
+}
+
+/// Read a BOM that is only partially buffered, or report an invalid one.
+fn yaml_parser_read_bom(reader: &mut dyn BufRead) -> Result<Option<Encoding>> {
+    let initial_bytes = reader.fill_buf()?;
     match initial_bytes[0] {
         0xef => {
             let mut bom = [0; 3];
@@ -52,7 +193,7 @@ fn yaml_parser_determine_encoding(reader: &mut dyn BufRead) -> Result<Option<Enc
 #[allow(unsafe_code)]
 fn read_utf8_buffered(
     reader: &mut dyn BufRead,
-    out: &mut VecDeque<char>,
+    out: &mut String,
     offset: &mut usize,
 ) -> Result<bool> {
     let available = loop {
@@ -121,7 +262,7 @@ fn read_utf8_buffered(
 
 fn read_utf8_char_unbuffered(
     reader: &mut dyn BufRead,
-    out: &mut VecDeque<char>,
+    out: &mut String,
     initial: u8,
     offset: &mut usize,
 ) -> Result<()> {
@@ -145,7 +286,7 @@ fn read_utf8_char_unbuffered(
 
 fn read_utf16_buffered<const BIG_ENDIAN: bool>(
     reader: &mut dyn BufRead,
-    out: &mut VecDeque<char>,
+    out: &mut String,
     offset: &mut usize,
 ) -> Result<bool> {
     let available = loop {
@@ -171,7 +312,7 @@ fn read_utf16_buffered<const BIG_ENDIAN: bool>(
         match ch {
             Ok(ch) => {
                 push_char(out, ch, *offset)?;
-                let n = ch.len_utf16();
+                let n = ch.len_utf16() * 2;
                 *offset += n;
                 used += n;
             }
@@ -189,10 +330,9 @@ fn read_utf16_buffered<const BIG_ENDIAN: bool>(
 
     if used != 0 {
         reader.consume(used);
-        *offset += used;
         Ok(true)
     } else {
-        debug_assert!(!available.is_empty() && available.len() < 2);
+        debug_assert!(!available.is_empty());
         read_utf16_char_unbuffered::<BIG_ENDIAN>(reader, out, offset)?;
         Ok(true)
     }
@@ -200,7 +340,7 @@ fn read_utf16_buffered<const BIG_ENDIAN: bool>(
 
 fn read_utf16_char_unbuffered<const BIG_ENDIAN: bool>(
     reader: &mut dyn BufRead,
-    out: &mut VecDeque<char>,
+    out: &mut String,
     offset: &mut usize,
 ) -> Result<()> {
     let mut buffer = [0; 2];
@@ -244,6 +384,65 @@ fn read_utf16_char_unbuffered<const BIG_ENDIAN: bool>(
     }
 }
 
+fn read_utf32_buffered<const BIG_ENDIAN: bool>(
+    reader: &mut dyn BufRead,
+    out: &mut String,
+    offset: &mut usize,
+) -> Result<bool> {
+    let available = loop {
+        match reader.fill_buf() {
+            Ok([]) => return Ok(false),
+            Ok(available) => break available,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    if available.len() < 4 {
+        // The buffer contains only part of a character. Read the whole
+        // character unbuffered.
+        let mut buffer = [0; 4];
+        let mut filled = 0;
+        while filled < 4 {
+            match reader.read(&mut buffer[filled..]) {
+                Ok(0) => return Err(Error::reader("incomplete UTF-32 character", *offset, -1)),
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        decode_utf32::<BIG_ENDIAN>(buffer, out, offset)?;
+        return Ok(true);
+    }
+
+    let mut used = 0;
+    for chunk in available.chunks_exact(4) {
+        let [a, b, c, d] = chunk else { unreachable!() };
+        decode_utf32::<BIG_ENDIAN>([*a, *b, *c, *d], out, offset)?;
+        used += 4;
+    }
+    reader.consume(used);
+    Ok(true)
+}
+
+fn decode_utf32<const BIG_ENDIAN: bool>(
+    bytes: [u8; 4],
+    out: &mut String,
+    offset: &mut usize,
+) -> Result<()> {
+    let value = if BIG_ENDIAN {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    };
+    let Some(ch) = char::from_u32(value) else {
+        return Err(Error::reader("invalid UTF-32", *offset, value as _));
+    };
+    push_char(out, ch, *offset)?;
+    *offset += 4;
+    Ok(())
+}
+
 fn utf8_char_width(initial: u8) -> usize {
     if initial & 0x80 == 0 {
         1
@@ -262,31 +461,72 @@ fn is_utf16_surrogate(value: u16) -> bool {
     matches!(value, 0xD800..=0xDFFF)
 }
 
-fn push_char(out: &mut VecDeque<char>, ch: char, offset: usize) -> Result<()> {
-    if !(ch == '\x09'
+fn is_allowed_char(ch: char) -> bool {
+    ch == '\x09'
         || ch == '\x0A'
         || ch == '\x0D'
         || ch >= '\x20' && ch <= '\x7E'
         || ch == '\u{0085}'
         || ch >= '\u{00A0}' && ch <= '\u{D7FF}'
         || ch >= '\u{E000}' && ch <= '\u{FFFD}'
-        || ch >= '\u{10000}' && ch <= '\u{10FFFF}')
-    {
+        || ch >= '\u{10000}' && ch <= '\u{10FFFF}'
+}
+
+fn push_char(out: &mut String, ch: char, offset: usize) -> Result<()> {
+    if !is_allowed_char(ch) {
         return Err(Error::reader(
             "control characters are not allowed",
             offset,
             ch as _,
         ));
     }
-    out.push_back(ch);
+    out.push(ch);
     Ok(())
 }
 
+/// Prepare an in-memory input, scanning it in place if possible.
+///
+/// Input that is not UTF-8, or that contains characters which are not allowed
+/// in a YAML stream, is read like any other input instead, so that errors are
+/// reported at the same position.
+pub(crate) fn yaml_parser_set_input_slice<'r>(parser: &mut Scanner<'r>, input: &'r [u8]) {
+    let mut rest = input;
+    let encoding = match parser.encoding {
+        Encoding::Any => match yaml_parser_determine_encoding(&mut rest) {
+            Ok(Some(encoding)) => Some(encoding),
+            Ok(None) => None,
+            Err(_) => Some(Encoding::Any),
+        },
+        encoding => Some(encoding),
+    };
+    if matches!(encoding, None | Some(Encoding::Utf8)) {
+        if let Ok(valid) = core::str::from_utf8(rest) {
+            let is_allowed_ascii = |byte| matches!(byte, b'\t' | b'\n' | b'\r' | b' '..=b'~');
+            if valid.bytes().all(is_allowed_ascii) || valid.chars().all(is_allowed_char) {
+                if let Some(encoding) = encoding {
+                    parser.encoding = encoding;
+                }
+                parser.eof = true;
+                parser.offset = input.len();
+                parser.buffer = InputBuffer::from_str(valid);
+                return;
+            }
+        }
+    }
+    parser.read_handler = Some(Input::Bytes(input));
+}
+
 pub(crate) fn yaml_parser_update_buffer(parser: &mut Scanner, length: usize) -> Result<()> {
-    let reader = parser.read_handler.as_deref_mut().expect("no read handler");
-    if parser.buffer.len() >= length {
+    if parser.buffer.is_cached(length) || parser.eof {
         return Ok(());
     }
+    let reader: &mut dyn BufRead = match parser.read_handler.as_mut().expect("no read handler") {
+        Input::Reader(reader) => *reader,
+        Input::Bytes(bytes) => bytes,
+    };
+    let Some(buffer) = parser.buffer.decoded_mut() else {
+        return Ok(());
+    };
     if parser.encoding == Encoding::Any {
         if let Some(encoding) = yaml_parser_determine_encoding(reader)? {
             parser.encoding = encoding;
@@ -296,21 +536,22 @@ pub(crate) fn yaml_parser_update_buffer(parser: &mut Scanner, length: usize) ->
         }
     }
 
-    while parser.buffer.len() < length {
+    while !has_chars(buffer, length) {
         if parser.eof {
             return Ok(());
         }
 
         let not_eof = match parser.encoding {
             Encoding::Any => unreachable!(),
-            Encoding::Utf8 => read_utf8_buffered(reader, &mut parser.buffer, &mut parser.offset)?,
-            Encoding::Utf16Le => {
-                read_utf16_buffered::<false>(reader, &mut parser.buffer, &mut parser.offset)?
-            }
-            Encoding::Utf16Be => {
-                read_utf16_buffered::<true>(reader, &mut parser.buffer, &mut parser.offset)?
-            }
+            Encoding::Utf8 => read_utf8_buffered(reader, buffer, &mut parser.offset)?,
+            Encoding::Utf16Le => read_utf16_buffered::<false>(reader, buffer, &mut parser.offset)?,
+            Encoding::Utf16Be => read_utf16_buffered::<true>(reader, buffer, &mut parser.offset)?,
+            Encoding::Utf32Le => read_utf32_buffered::<false>(reader, buffer, &mut parser.offset)?,
+            Encoding::Utf32Be => read_utf32_buffered::<true>(reader, buffer, &mut parser.offset)?,
         };
+        if ParserLimits::exceeds(parser.limits.max_input_bytes, parser.offset) {
+            return Err(Error::limit_exceeded(Limit::InputBytes, parser.mark));
+        }
         if !not_eof {
             parser.eof = true;
             return Ok(());
@@ -1,11 +1,13 @@
 use std::collections::VecDeque;
 
+use alloc::borrow::Cow;
 use alloc::string::String;
 
 use crate::macros::{is_blankz, is_break};
-use crate::reader::yaml_parser_update_buffer;
+use crate::reader::{yaml_parser_set_input_slice, yaml_parser_update_buffer, Input, InputBuffer};
 use crate::{
-    Encoding, Error, Mark, Result, ScalarStyle, SimpleKey, Token, TokenData, INPUT_BUFFER_SIZE,
+    BorrowedToken, Encoding, Error, Limit, Mark, ParserLimits, Result, ScalarStyle, SimpleKey,
+    Token, TokenData,
 };
 
 const MAX_NUMBER_LENGTH: u64 = 9_u64;
@@ -16,13 +18,13 @@ const MAX_NUMBER_LENGTH: u64 = 9_u64;
 /// replacement for the libyaml `yaml_parser_scan()` function.
 pub struct Scanner<'r> {
     /// Read handler.
-    pub(crate) read_handler: Option<&'r mut dyn std::io::BufRead>,
+    pub(crate) read_handler: Option<Input<'r>>,
     /// EOF flag
     pub(crate) eof: bool,
     /// The working buffer.
     ///
     /// This always contains valid UTF-8.
-    pub(crate) buffer: VecDeque<char>,
+    pub(crate) buffer: InputBuffer<'r>,
     /// The input encoding.
     pub(crate) encoding: Encoding,
     /// The offset of the current position (in bytes).
@@ -36,7 +38,7 @@ pub struct Scanner<'r> {
     /// The number of unclosed '[' and '{' indicators.
     pub(crate) flow_level: i32,
     /// The tokens queue.
-    pub(crate) tokens: VecDeque<Token>,
+    pub(crate) tokens: VecDeque<BorrowedToken<'r>>,
     /// The number of tokens fetched from the queue.
     pub(crate) tokens_parsed: usize,
     /// Does the tokens queue contain a token ready for dequeueing.
@@ -49,6 +51,13 @@ pub struct Scanner<'r> {
     pub(crate) simple_key_allowed: bool,
     /// The stack of simple keys.
     pub(crate) simple_keys: Vec<SimpleKey>,
+    /// Produce COMMENT tokens instead of skipping comments?
+    pub(crate) preserve_comments: bool,
+    /// The line on which the last scanned token ended, if a comment following
+    /// it on that line is inline.
+    pub(crate) last_token_line: Option<u64>,
+    /// The resource limits.
+    pub(crate) limits: ParserLimits,
 }
 
 impl<'r> Scanner<'r> {
@@ -56,7 +65,7 @@ impl<'r> Scanner<'r> {
         Self {
             read_handler: None,
             eof: false,
-            buffer: VecDeque::with_capacity(INPUT_BUFFER_SIZE),
+            buffer: InputBuffer::new(),
             encoding: Encoding::Any,
             offset: 0,
             mark: Mark::default(),
@@ -70,19 +79,32 @@ impl<'r> Scanner<'r> {
             indent: 0,
             simple_key_allowed: false,
             simple_keys: Vec::with_capacity(16),
+            preserve_comments: false,
+            last_token_line: None,
+            limits: ParserLimits::default(),
         }
     }
 
     /// Set a string input.
     pub fn set_input_string(&mut self, input: &'r mut &[u8]) {
         assert!((self.read_handler).is_none());
-        self.read_handler = Some(input);
+        self.read_handler = Some(Input::Reader(input));
+    }
+
+    /// Set an in-memory input.
+    ///
+    /// Unlike [`Scanner::set_input_string()`], UTF-8 input is scanned in place,
+    /// without copying it into an intermediate buffer first. Other input is
+    /// read as usual.
+    pub fn set_input_slice(&mut self, input: &'r [u8]) {
+        assert!((self.read_handler).is_none());
+        yaml_parser_set_input_slice(self, input);
     }
 
     /// Set a generic input handler.
     pub fn set_input(&mut self, input: &'r mut dyn std::io::BufRead) {
         assert!((self.read_handler).is_none());
-        self.read_handler = Some(input);
+        self.read_handler = Some(Input::Reader(input));
     }
 
     /// Set the source encoding.
@@ -91,8 +113,59 @@ impl<'r> Scanner<'r> {
         self.encoding = encoding;
     }
 
+    /// Set if comments should be preserved.
+    ///
+    /// When enabled, the scanner produces a [`TokenData::Comment`] token for
+    /// each comment in the input instead of skipping it. Comments that follow
+    /// a directive or a block scalar header on the same line are produced
+    /// immediately after the directive or scalar token, and comments that
+    /// follow a ',' in a flow collection immediately before the FLOW-ENTRY
+    /// token.
+    pub fn set_preserve_comments(&mut self, preserve: bool) {
+        self.preserve_comments = preserve;
+    }
+
+    /// Set the resource limits.
+    ///
+    /// The scanner enforces the limits on the nesting depth, scalar length,
+    /// and input size. The other limits are enforced by the [`Parser`].
+    ///
+    /// [`Parser`]: crate::Parser
+    pub fn set_limits(&mut self, limits: ParserLimits) {
+        self.limits = limits;
+    }
+
+    /// Fail if the input read so far exceeds the maximum input size.
+    fn check_input_bytes(&self) -> Result<()> {
+        if ParserLimits::exceeds(self.limits.max_input_bytes, self.offset) {
+            return Err(Error::limit_exceeded(Limit::InputBytes, self.mark));
+        }
+        Ok(())
+    }
+
+    /// Fail if opening another collection exceeds the maximum nesting depth.
+    fn check_depth(&self) -> Result<()> {
+        let depth = self.indents.len() + self.flow_level as usize + 1;
+        if ParserLimits::exceeds(self.limits.max_depth, depth) {
+            return Err(Error::limit_exceeded(Limit::Depth, self.mark));
+        }
+        Ok(())
+    }
+
+    /// Fail if the scalar starting at `start_mark` exceeds the maximum scalar
+    /// length.
+    ///
+    /// Called while the scalar is scanned, so that a long scalar is rejected
+    /// before it is read into memory.
+    fn check_scalar_length(&self, length: usize, start_mark: Mark) -> Result<()> {
+        if ParserLimits::exceeds(self.limits.max_scalar_length, length) {
+            return Err(Error::limit_exceeded(Limit::ScalarLength, start_mark));
+        }
+        Ok(())
+    }
+
     fn cache(&mut self, length: usize) -> Result<()> {
-        if self.buffer.len() >= length {
+        if self.buffer.is_cached(length) {
             Ok(())
         } else {
             yaml_parser_update_buffer(self, length)
@@ -109,12 +182,12 @@ impl<'r> Scanner<'r> {
 
     /// Equivalent to the libyaml macro `SKIP_LINE`.
     fn skip_line_break(&mut self) {
-        if let Some(front) = self.buffer.front().copied() {
-            if let ('\r', Some('\n')) = (front, self.buffer.get(1).copied()) {
+        if let Some(front) = self.buffer.get(0) {
+            if let ('\r', Some('\n')) = (front, self.buffer.get(1)) {
                 self.mark.index += 2;
                 self.mark.column = 0;
                 self.mark.line += 1;
-                self.buffer.drain(0..2);
+                self.buffer.skip(2);
             } else if is_break(front) {
                 let width = front.len_utf8();
                 self.mark.index += width as u64;
@@ -125,10 +198,22 @@ impl<'r> Scanner<'r> {
         }
     }
 
+    /// Start a value that is read from the input.
+    fn new_value(&self) -> ValueBuilder<'r> {
+        match self.buffer.borrowed() {
+            Some((input, pos)) => ValueBuilder::Slice {
+                input,
+                start: pos,
+                end: pos,
+            },
+            None => ValueBuilder::Owned(String::new()),
+        }
+    }
+
     /// Equivalent to the libyaml macro `READ`.
-    fn read_char(&mut self, string: &mut String) {
+    fn read_char(&mut self, string: &mut impl PushStr) {
         if let Some(popped) = self.buffer.pop_front() {
-            string.push(popped);
+            string.push_str(popped.encode_utf8(&mut [0; 4]));
             self.mark.index += popped.len_utf8() as u64;
             self.mark.column += 1;
         } else {
@@ -136,15 +221,32 @@ impl<'r> Scanner<'r> {
         }
     }
 
+    /// Skip the ASCII characters matching `predicate`, at once. This is a
+    /// shortcut for calling [`Scanner::skip_char()`] in a loop, and stops at
+    /// the end of the buffered input.
+    fn skip_ascii_while(&mut self, predicate: impl Fn(u8) -> bool) {
+        let len = self.buffer.take_ascii_while(predicate).len();
+        self.mark.index += len as u64;
+        self.mark.column += len as u64;
+    }
+
+    /// Like [`Scanner::skip_ascii_while()`], but for [`Scanner::read_char()`].
+    fn read_ascii_while(&mut self, string: &mut impl PushStr, predicate: impl Fn(u8) -> bool) {
+        let run = self.buffer.take_ascii_while(predicate);
+        string.push_str(run);
+        self.mark.index += run.len() as u64;
+        self.mark.column += run.len() as u64;
+    }
+
     /// Equivalent to the libyaml macro `READ_LINE`.
     fn read_line_break(&mut self, string: &mut String) {
-        let Some(front) = self.buffer.front().copied() else {
+        let Some(front) = self.buffer.get(0) else {
             panic!("unexpected end of input");
         };
 
-        if let Some('\r') = self.buffer.get(1).copied() {
+        if let Some('\r') = self.buffer.get(1) {
             string.push('\n');
-            self.buffer.drain(0..2);
+            self.buffer.skip(2);
             self.mark.index += 2;
             self.mark.column = 0;
             self.mark.line += 1;
@@ -170,6 +272,17 @@ impl<'r> Scanner<'r> {
     /// [`TokenData::StreamStart`] while the ending token has the type
     /// [`TokenData::StreamEnd`].
     pub fn scan(&mut self) -> Result<Token> {
+        self.scan_borrowed().map(BorrowedToken::into_owned)
+    }
+
+    /// Like [`Scanner::scan()`], but borrow the values of the token from the
+    /// input where possible.
+    ///
+    /// Values are borrowed when the input was set with
+    /// [`Scanner::set_input_slice()`] and the value appears verbatim in the
+    /// input: anchors, aliases, and comments, tags without URI escapes, and
+    /// plain and quoted scalars without escape sequences or line folding.
+    pub fn scan_borrowed(&mut self) -> Result<BorrowedToken<'r>> {
         if self.stream_end_produced {
             return Ok(Token {
                 data: TokenData::StreamEnd,
@@ -193,7 +306,7 @@ impl<'r> Scanner<'r> {
     }
 
     /// Equivalent of the libyaml `PEEK_TOKEN` macro, used by the parser.
-    pub(crate) fn peek(&mut self) -> Result<&Token> {
+    pub(crate) fn peek(&mut self) -> Result<&BorrowedToken<'r>> {
         if self.token_available {
             return Ok(self
                 .tokens
@@ -212,7 +325,7 @@ impl<'r> Scanner<'r> {
     }
 
     /// Equivalent of the libyaml `PEEK_TOKEN` macro, used by the parser.
-    pub(crate) fn peek_mut(&mut self) -> Result<&mut Token> {
+    pub(crate) fn peek_mut(&mut self) -> Result<&mut BorrowedToken<'r>> {
         if self.token_available {
             return Ok(self
                 .tokens
@@ -232,6 +345,11 @@ impl<'r> Scanner<'r> {
 
     /// Equivalent of the libyaml `SKIP_TOKEN` macro, used by the parser.
     pub(crate) fn skip_token(&mut self) {
+        _ = self.take_token();
+    }
+
+    /// Like [`Scanner::skip_token()`], but return the skipped token.
+    pub(crate) fn take_token(&mut self) -> BorrowedToken<'r> {
         self.token_available = false;
         self.tokens_parsed = self.tokens_parsed.wrapping_add(1);
         let skipped = self.tokens.pop_front().expect("SKIP_TOKEN but EOF");
@@ -242,6 +360,7 @@ impl<'r> Scanner<'r> {
                 ..
             }
         );
+        skipped
     }
 
     fn set_scanner_error<T>(
@@ -272,6 +391,17 @@ impl<'r> Scanner<'r> {
                 break;
             }
             self.fetch_next_token()?;
+            if self.preserve_comments {
+                self.last_token_line = self.tokens.back().and_then(|token| match token.data {
+                    // Block scalars end after their trailing line breaks.
+                    TokenData::StreamStart { .. }
+                    | TokenData::Scalar {
+                        style: ScalarStyle::Literal | ScalarStyle::Folded,
+                        ..
+                    } => None,
+                    _ => Some(token.end_mark.line),
+                });
+            }
         }
         self.token_available = true;
         Ok(())
@@ -280,6 +410,7 @@ impl<'r> Scanner<'r> {
     fn fetch_next_token(&mut self) -> Result<()> {
         self.cache(1)?;
         if !self.stream_start_produced {
+            self.check_input_bytes()?;
             self.fetch_stream_start();
             return Ok(());
         }
@@ -290,14 +421,14 @@ impl<'r> Scanner<'r> {
         if IS_Z!(self.buffer) {
             return self.fetch_stream_end();
         }
-        if self.mark.column == 0_u64 && self.buffer[0] == '%' {
+        if self.mark.column == 0_u64 && CHECK!(self.buffer, '%') {
             return self.fetch_directive();
         }
         if self.mark.column == 0_u64
             && CHECK_AT!(self.buffer, '-', 0)
             && CHECK_AT!(self.buffer, '-', 1)
             && CHECK_AT!(self.buffer, '-', 2)
-            && is_blankz(self.buffer.get(3).copied())
+            && is_blankz(self.buffer.get(3))
         {
             return self.fetch_document_indicator(TokenData::DocumentStart);
         }
@@ -305,7 +436,7 @@ impl<'r> Scanner<'r> {
             && CHECK_AT!(self.buffer, '.', 0)
             && CHECK_AT!(self.buffer, '.', 1)
             && CHECK_AT!(self.buffer, '.', 2)
-            && is_blankz(self.buffer.get(3).copied())
+            && is_blankz(self.buffer.get(3))
         {
             return self.fetch_document_indicator(TokenData::DocumentEnd);
         }
@@ -450,6 +581,7 @@ impl<'r> Scanner<'r> {
                 column: 0_u64,
             },
         };
+        self.check_depth()?;
         self.simple_keys.push(empty_simple_key);
         assert!(
             self.flow_level != i32::MAX,
@@ -466,11 +598,18 @@ impl<'r> Scanner<'r> {
         }
     }
 
-    fn roll_indent(&mut self, column: i64, number: i64, data: TokenData, mark: Mark) -> Result<()> {
+    fn roll_indent(
+        &mut self,
+        column: i64,
+        number: i64,
+        data: TokenData<Cow<'r, str>>,
+        mark: Mark,
+    ) -> Result<()> {
         if self.flow_level != 0 {
             return Ok(());
         }
         if self.indent < column as i32 {
+            self.check_depth()?;
             self.indents.push(self.indent);
             assert!(column <= i32::MAX as i64, "integer overflow");
             self.indent = column as i32;
@@ -550,12 +689,13 @@ impl<'r> Scanner<'r> {
         self.unroll_indent(-1_i64);
         self.remove_simple_key()?;
         self.simple_key_allowed = false;
-        let token = self.scan_directive()?;
+        let (token, comment) = self.scan_directive()?;
         self.tokens.push_back(token);
+        self.tokens.extend(comment);
         Ok(())
     }
 
-    fn fetch_document_indicator(&mut self, data: TokenData) -> Result<()> {
+    fn fetch_document_indicator(&mut self, data: TokenData<Cow<'r, str>>) -> Result<()> {
         self.unroll_indent(-1_i64);
         self.remove_simple_key()?;
         self.simple_key_allowed = false;
@@ -574,7 +714,7 @@ impl<'r> Scanner<'r> {
         Ok(())
     }
 
-    fn fetch_flow_collection_start(&mut self, data: TokenData) -> Result<()> {
+    fn fetch_flow_collection_start(&mut self, data: TokenData<Cow<'r, str>>) -> Result<()> {
         self.save_simple_key()?;
         self.increase_flow_level()?;
         self.simple_key_allowed = true;
@@ -590,7 +730,7 @@ impl<'r> Scanner<'r> {
         Ok(())
     }
 
-    fn fetch_flow_collection_end(&mut self, data: TokenData) -> Result<()> {
+    fn fetch_flow_collection_end(&mut self, data: TokenData<Cow<'r, str>>) -> Result<()> {
         self.remove_simple_key()?;
         self.decrease_flow_level();
         self.simple_key_allowed = false;
@@ -617,6 +757,18 @@ impl<'r> Scanner<'r> {
             start_mark,
             end_mark,
         };
+        if self.preserve_comments {
+            // A comment after ',' follows the entry that ',' ends, so produce
+            // it before the FLOW-ENTRY token.
+            self.cache(1)?;
+            while IS_BLANK!(self.buffer) {
+                self.skip_char();
+                self.cache(1)?;
+            }
+            if let Some(comment) = self.scan_comment(true)? {
+                self.tokens.push_back(comment);
+            }
+        }
         self.tokens.push_back(token);
         Ok(())
     }
@@ -753,8 +905,9 @@ impl<'r> Scanner<'r> {
     fn fetch_block_scalar(&mut self, literal: bool) -> Result<()> {
         self.remove_simple_key()?;
         self.simple_key_allowed = true;
-        let token = self.scan_block_scalar(literal)?;
+        let (token, comment) = self.scan_block_scalar(literal)?;
         self.tokens.push_back(token);
+        self.tokens.extend(comment);
         Ok(())
     }
 
@@ -775,6 +928,8 @@ impl<'r> Scanner<'r> {
     }
 
     fn scan_to_next_token(&mut self) -> Result<()> {
+        // A comment is inline if it shares its line with the preceding token.
+        let mut inline = self.last_token_line == Some(self.mark.line);
         loop {
             self.cache(1)?;
             if self.mark.column == 0 && IS_BOM!(self.buffer) {
@@ -785,19 +940,18 @@ impl<'r> Scanner<'r> {
                 || (self.flow_level != 0 || !self.simple_key_allowed) && CHECK!(self.buffer, '\t')
             {
                 self.skip_char();
+                self.skip_ascii_while(|byte| byte == b' ');
                 self.cache(1)?;
             }
-            if CHECK!(self.buffer, '#') {
-                while !IS_BREAKZ!(self.buffer) {
-                    self.skip_char();
-                    self.cache(1)?;
-                }
+            if let Some(comment) = self.scan_comment(inline)? {
+                self.tokens.push_back(comment);
             }
             if !IS_BREAK!(self.buffer) {
                 break;
             }
             self.cache(2)?;
             self.skip_line_break();
+            inline = false;
             if self.flow_level == 0 {
                 self.simple_key_allowed = true;
             }
@@ -805,7 +959,43 @@ impl<'r> Scanner<'r> {
         Ok(())
     }
 
-    fn scan_directive(&mut self) -> Result<Token> {
+    /// Skip a comment, if there is one at the current position, or scan it
+    /// into a COMMENT token when comments are preserved.
+    fn scan_comment(&mut self, inline: bool) -> Result<Option<BorrowedToken<'r>>> {
+        if !CHECK!(self.buffer, '#') {
+            return Ok(None);
+        }
+        let is_text = |byte| !matches!(byte, b'\r' | b'\n');
+        if !self.preserve_comments {
+            while !IS_BREAKZ!(self.buffer) {
+                self.skip_char();
+                self.skip_ascii_while(is_text);
+                self.cache(1)?;
+            }
+            return Ok(None);
+        }
+        let start_mark: Mark = self.mark;
+        self.skip_char();
+        let mut string = self.new_value();
+        self.cache(1)?;
+        while !IS_BREAKZ!(self.buffer) {
+            self.read_char(&mut string);
+            self.read_ascii_while(&mut string, is_text);
+            self.cache(1)?;
+        }
+        let end_mark: Mark = self.mark;
+        Ok(Some(Token {
+            data: TokenData::Comment {
+                value: string.into_cow(),
+                inline,
+            },
+            start_mark,
+            end_mark,
+        }))
+    }
+
+    // Returns the directive token and the comment following it, if any.
+    fn scan_directive(&mut self) -> Result<(BorrowedToken<'r>, Option<BorrowedToken<'r>>)> {
         let end_mark: Mark;
         let mut major: i32 = 0;
         let mut minor: i32 = 0;
@@ -845,22 +1035,14 @@ impl<'r> Scanner<'r> {
             self.cache(1)?;
         }
 
-        if CHECK!(self.buffer, '#') {
-            loop {
-                if IS_BREAKZ!(self.buffer) {
-                    break;
-                }
-                self.skip_char();
-                self.cache(1)?;
-            }
-        }
+        let comment = self.scan_comment(true)?;
 
         if IS_BREAKZ!(self.buffer) {
             if IS_BREAK!(self.buffer) {
                 self.cache(2)?;
                 self.skip_line_break();
             }
-            Ok(token)
+            Ok((token, comment))
         } else {
             self.set_scanner_error(
                 "while scanning a directive",
@@ -951,7 +1133,10 @@ impl<'r> Scanner<'r> {
     }
 
     // Returns (handle, prefix)
-    fn scan_tag_directive_value(&mut self, start_mark: Mark) -> Result<(String, String)> {
+    fn scan_tag_directive_value(
+        &mut self,
+        start_mark: Mark,
+    ) -> Result<(Cow<'r, str>, Cow<'r, str>)> {
         self.cache(1)?;
 
         loop {
@@ -991,12 +1176,12 @@ impl<'r> Scanner<'r> {
         }
     }
 
-    fn scan_anchor(&mut self, scan_alias_instead_of_anchor: bool) -> Result<Token> {
+    fn scan_anchor(&mut self, scan_alias_instead_of_anchor: bool) -> Result<BorrowedToken<'r>> {
         let mut length: i32 = 0;
 
-        let mut string = String::new();
         let start_mark: Mark = self.mark;
         self.skip_char();
+        let mut string = self.new_value();
         self.cache(1)?;
 
         loop {
@@ -1031,9 +1216,13 @@ impl<'r> Scanner<'r> {
         } else {
             Ok(Token {
                 data: if scan_alias_instead_of_anchor {
-                    TokenData::Alias { value: string }
+                    TokenData::Alias {
+                        value: string.into_cow(),
+                    }
                 } else {
-                    TokenData::Anchor { value: string }
+                    TokenData::Anchor {
+                        value: string.into_cow(),
+                    }
                 },
                 start_mark,
                 end_mark,
@@ -1041,7 +1230,7 @@ impl<'r> Scanner<'r> {
         }
     }
 
-    fn scan_tag(&mut self) -> Result<Token> {
+    fn scan_tag(&mut self) -> Result<BorrowedToken<'r>> {
         let mut handle;
         let mut suffix;
 
@@ -1050,7 +1239,7 @@ impl<'r> Scanner<'r> {
         self.cache(2)?;
 
         if CHECK_AT!(self.buffer, '<', 1) {
-            handle = String::new();
+            handle = Cow::Borrowed("");
             self.skip_char();
             self.skip_char();
             suffix = self.scan_tag_uri(true, false, None, start_mark)?;
@@ -1069,7 +1258,7 @@ impl<'r> Scanner<'r> {
                 suffix = self.scan_tag_uri(false, false, None, start_mark)?;
             } else {
                 suffix = self.scan_tag_uri(false, false, Some(&handle), start_mark)?;
-                handle = String::from("!");
+                handle = Cow::Borrowed("!");
                 if suffix.is_empty() {
                     core::mem::swap(&mut handle, &mut suffix);
                 }
@@ -1096,8 +1285,8 @@ impl<'r> Scanner<'r> {
         })
     }
 
-    fn scan_tag_handle(&mut self, directive: bool, start_mark: Mark) -> Result<String> {
-        let mut string = String::new();
+    fn scan_tag_handle(&mut self, directive: bool, start_mark: Mark) -> Result<Cow<'r, str>> {
+        let mut string = self.new_value();
         self.cache(1)?;
 
         if !CHECK!(self.buffer, '!') {
@@ -1123,14 +1312,14 @@ impl<'r> Scanner<'r> {
         }
         if CHECK!(self.buffer, '!') {
             self.read_char(&mut string);
-        } else if directive && string != "!" {
+        } else if directive && string.as_str() != "!" {
             return self.set_scanner_error(
                 "while parsing a tag directive",
                 start_mark,
                 "did not find expected '!'",
             );
         }
-        Ok(string)
+        Ok(string.into_cow())
     }
 
     fn scan_tag_uri(
@@ -1139,13 +1328,13 @@ impl<'r> Scanner<'r> {
         directive: bool,
         head: Option<&str>,
         start_mark: Mark,
-    ) -> Result<String> {
+    ) -> Result<Cow<'r, str>> {
         let head = head.unwrap_or("");
         let mut length = head.len();
-        let mut string = String::new();
+        let mut string = self.new_value();
 
         if length > 1 {
-            string = String::from(&head[1..]);
+            string.push_str(&head[1..]);
         }
         self.cache(1)?;
 
@@ -1173,7 +1362,7 @@ impl<'r> Scanner<'r> {
                     || CHECK!(self.buffer, ']'))
         {
             if CHECK!(self.buffer, '%') {
-                self.scan_uri_escapes(directive, start_mark, &mut string)?;
+                self.scan_uri_escapes(directive, start_mark, string.to_mut())?;
             } else {
                 self.read_char(&mut string);
             }
@@ -1191,7 +1380,7 @@ impl<'r> Scanner<'r> {
                 "did not find expected tag URI",
             )
         } else {
-            Ok(string)
+            Ok(string.into_cow())
         }
     }
 
@@ -1266,7 +1455,12 @@ impl<'r> Scanner<'r> {
         Ok(())
     }
 
-    fn scan_block_scalar(&mut self, literal: bool) -> Result<Token> {
+    // Returns the scalar token and the comment following the block scalar
+    // header, if any.
+    fn scan_block_scalar(
+        &mut self,
+        literal: bool,
+    ) -> Result<(BorrowedToken<'r>, Option<BorrowedToken<'r>>)> {
         let mut end_mark: Mark;
         let mut string = String::new();
         let mut leading_break = String::new();
@@ -1321,15 +1515,7 @@ impl<'r> Scanner<'r> {
             self.cache(1)?;
         }
 
-        if CHECK!(self.buffer, '#') {
-            loop {
-                if IS_BREAKZ!(self.buffer) {
-                    break;
-                }
-                self.skip_char();
-                self.cache(1)?;
-            }
-        }
+        let comment = self.scan_comment(true)?;
 
         if !IS_BREAKZ!(self.buffer) {
             return self.set_scanner_error(
@@ -1384,6 +1570,7 @@ impl<'r> Scanner<'r> {
             leading_blank = IS_BLANK!(self.buffer) as i32;
             while !IS_BREAKZ!(self.buffer) {
                 self.read_char(&mut string);
+                self.check_scalar_length(string.len(), start_mark)?;
                 self.cache(1)?;
             }
             self.cache(2)?;
@@ -1403,10 +1590,11 @@ impl<'r> Scanner<'r> {
         if chomping == 1 {
             string.push_str(&trailing_breaks);
         }
+        self.check_scalar_length(string.len(), start_mark)?;
 
-        Ok(Token {
+        let token = Token {
             data: TokenData::Scalar {
-                value: string,
+                value: string.into(),
                 style: if literal {
                     ScalarStyle::Literal
                 } else {
@@ -1415,7 +1603,8 @@ impl<'r> Scanner<'r> {
             },
             start_mark,
             end_mark,
-        })
+        };
+        Ok((token, comment))
     }
 
     fn scan_block_scalar_breaks(
@@ -1462,8 +1651,7 @@ impl<'r> Scanner<'r> {
         Ok(())
     }
 
-    fn scan_flow_scalar(&mut self, single: bool) -> Result<Token> {
-        let mut string = String::new();
+    fn scan_flow_scalar(&mut self, single: bool) -> Result<BorrowedToken<'r>> {
         let mut leading_break = String::new();
         let mut trailing_breaks = String::new();
         let mut whitespaces = String::new();
@@ -1471,6 +1659,7 @@ impl<'r> Scanner<'r> {
 
         let start_mark: Mark = self.mark;
         self.skip_char();
+        let mut string = self.new_value();
         loop {
             self.cache(4)?;
 
@@ -1514,7 +1703,7 @@ impl<'r> Scanner<'r> {
                         break;
                     } else if !single && CHECK!(self.buffer, '\\') {
                         let mut code_length = 0usize;
-                        match self.buffer.get(1).copied().unwrap() {
+                        match self.buffer.get(1).unwrap() {
                             '0' => {
                                 string.push('\0');
                             }
@@ -1632,6 +1821,7 @@ impl<'r> Scanner<'r> {
                         self.read_char(&mut string);
                     }
                 }
+                self.check_scalar_length(string.len(), start_mark)?;
                 self.cache(2)?;
             }
             self.cache(1)?;
@@ -1677,13 +1867,14 @@ impl<'r> Scanner<'r> {
                 string.push_str(&whitespaces);
                 whitespaces.clear();
             }
+            self.check_scalar_length(string.len(), start_mark)?;
         }
 
         self.skip_char();
         let end_mark: Mark = self.mark;
         Ok(Token {
             data: TokenData::Scalar {
-                value: string,
+                value: string.into_cow(),
                 style: if single {
                     ScalarStyle::SingleQuoted
                 } else {
@@ -1695,9 +1886,9 @@ impl<'r> Scanner<'r> {
         })
     }
 
-    fn scan_plain_scalar(&mut self) -> Result<Token> {
+    fn scan_plain_scalar(&mut self) -> Result<BorrowedToken<'r>> {
         let mut end_mark: Mark;
-        let mut string = String::new();
+        let mut string = self.new_value();
         let mut leading_break = String::new();
         let mut trailing_breaks = String::new();
         let mut whitespaces = String::new();
@@ -1771,6 +1962,11 @@ impl<'r> Scanner<'r> {
                     }
                 }
                 self.read_char(&mut string);
+                self.read_ascii_while(&mut string, |byte| {
+                    byte.is_ascii_graphic()
+                        && !matches!(byte, b':' | b',' | b'[' | b']' | b'{' | b'}')
+                });
+                self.check_scalar_length(string.len(), start_mark)?;
                 end_mark = self.mark;
                 self.cache(2)?;
             }
@@ -1790,8 +1986,12 @@ impl<'r> Scanner<'r> {
                         );
                     } else if !leading_blanks {
                         self.read_char(&mut whitespaces);
+                        self.read_ascii_while(&mut whitespaces, |byte| {
+                            byte == b' ' || byte == b'\t'
+                        });
                     } else {
                         self.skip_char();
+                        self.skip_ascii_while(|byte| byte == b' ');
                     }
                 } else {
                     self.cache(2)?;
@@ -1817,7 +2017,7 @@ impl<'r> Scanner<'r> {
 
         Ok(Token {
             data: TokenData::Scalar {
-                value: string,
+                value: string.into_cow(),
                 style: ScalarStyle::Plain,
             },
             start_mark,
@@ -1845,3 +2045,78 @@ impl<'r> Iterator for Scanner<'r> {
 }
 
 impl<'r> core::iter::FusedIterator for Scanner<'r> {}
+
+/// A string that characters of the input are read into.
+trait PushStr {
+    fn push_str(&mut self, string: &str);
+}
+
+impl PushStr for String {
+    fn push_str(&mut self, string: &str) {
+        String::push_str(self, string);
+    }
+}
+
+/// A token value being scanned.
+///
+/// For as long as the value is a verbatim slice of an in-memory input, it
+/// borrows from the input instead of copying it.
+enum ValueBuilder<'r> {
+    Slice {
+        input: &'r str,
+        start: usize,
+        end: usize,
+    },
+    Owned(String),
+}
+
+impl<'r> ValueBuilder<'r> {
+    fn push(&mut self, ch: char) {
+        self.push_str(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Slice { start, end, .. } => end - start,
+            Self::Owned(owned) => owned.len(),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Slice { input, start, end } => &input[*start..*end],
+            Self::Owned(owned) => owned,
+        }
+    }
+
+    /// Copy the value, so that it can be modified freely.
+    fn to_mut(&mut self) -> &mut String {
+        if let Self::Slice { input, start, end } = *self {
+            *self = Self::Owned(String::from(&input[start..end]));
+        }
+        let Self::Owned(owned) = self else {
+            unreachable!()
+        };
+        owned
+    }
+
+    fn into_cow(self) -> Cow<'r, str> {
+        match self {
+            Self::Slice { input, start, end } => Cow::Borrowed(&input[start..end]),
+            Self::Owned(owned) => Cow::Owned(owned),
+        }
+    }
+}
+
+impl PushStr for ValueBuilder<'_> {
+    fn push_str(&mut self, string: &str) {
+        match self {
+            // The value stays verbatim if the input continues with `string`,
+            // wherever `string` comes from.
+            Self::Slice { input, end, .. } if input[*end..].starts_with(string) => {
+                *end += string.len();
+            }
+            _ => self.to_mut().push_str(string),
+        }
+    }
+}
@@ -0,0 +1,425 @@
+//! Resolution of plain scalars to the types of the YAML schemas.
+
+use crate::{BOOL_TAG, FLOAT_TAG, INT_TAG, NULL_TAG, STR_TAG, TIMESTAMP_TAG};
+
+/// A schema, which resolves the tags of plain scalars without an explicit tag.
+///
+/// Quoted and block scalars without an explicit tag are always strings
+/// (`!!str`). See [`Parser::set_schema()`](crate::Parser::set_schema).
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum Schema {
+    /// The YAML 1.2 failsafe schema: every scalar is a string (`!!str`).
+    #[default]
+    Failsafe,
+    /// The YAML 1.2 JSON schema: `null`, `true`, `false`, and JSON numbers.
+    ///
+    /// Any other plain scalar is an error.
+    Json,
+    /// The YAML 1.2 core schema: the JSON schema extended with the
+    /// capitalized forms of `null`, `true` and `false`, `~`, the empty value,
+    /// octal (`0o`) and hexadecimal (`0x`) integers, and `.inf` and `.nan`.
+    /// Any other plain scalar is a string.
+    Core,
+    /// The types of YAML 1.1: additionally `yes`, `no`, `on` and `off` as
+    /// booleans, binary, octal (leading `0`) and sexagesimal numbers,
+    /// underscores in numbers, and timestamps (`!!timestamp`).
+    Yaml11,
+}
+
+impl Schema {
+    /// Resolve the tag of the plain scalar `value`.
+    ///
+    /// Returns `None` if `value` matches none of the types of the schema,
+    /// which only happens with [`Schema::Json`].
+    pub fn resolve(self, value: &str) -> Option<&'static str> {
+        let tag = match self {
+            Schema::Failsafe => STR_TAG,
+            Schema::Json => match value {
+                "null" => NULL_TAG,
+                "true" | "false" => BOOL_TAG,
+                _ if is_json_int(value) => INT_TAG,
+                _ if is_json_float(value) => FLOAT_TAG,
+                _ => return None,
+            },
+            Schema::Core => {
+                if is_null(value) {
+                    NULL_TAG
+                } else if parse_bool(value).is_some() {
+                    BOOL_TAG
+                } else if is_core_int(value) {
+                    INT_TAG
+                } else if parse_float(value).is_some() {
+                    FLOAT_TAG
+                } else {
+                    STR_TAG
+                }
+            }
+            Schema::Yaml11 => {
+                if is_null(value) {
+                    NULL_TAG
+                } else if parse_yaml11_bool(value).is_some() {
+                    BOOL_TAG
+                } else if is_yaml11_int(value) {
+                    INT_TAG
+                } else if is_yaml11_float(value) {
+                    FLOAT_TAG
+                } else if is_yaml11_timestamp(value) {
+                    TIMESTAMP_TAG
+                } else {
+                    STR_TAG
+                }
+            }
+        };
+        Some(tag)
+    }
+}
+
+/// Does the plain scalar `value` resolve to `!!null`?
+pub(crate) fn is_null(value: &str) -> bool {
+    matches!(value, "" | "~" | "null" | "Null" | "NULL")
+}
+
+/// Parse a `!!bool` value.
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "True" | "TRUE" => Some(true),
+        "false" | "False" | "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parse an `!!int` value: a decimal integer with an optional sign, or an
+/// octal (`0o`) or hexadecimal (`0x`) integer.
+///
+/// Returns `None` if the value is not an integer, or if it does not fit in an
+/// `i128`.
+pub(crate) fn parse_int(value: &str) -> Option<i128> {
+    if let Some(hex) = value.strip_prefix("0x") {
+        return parse_digits(hex, 16);
+    }
+    if let Some(octal) = value.strip_prefix("0o") {
+        return parse_digits(octal, 8);
+    }
+    let (negative, digits) = strip_sign(value);
+    let magnitude = parse_digits(digits, 10)?;
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// Parse a `!!float` value, including `.inf` and `.nan`.
+pub(crate) fn parse_float(value: &str) -> Option<f64> {
+    if matches!(value, ".nan" | ".NaN" | ".NAN") {
+        return Some(f64::NAN);
+    }
+    let (negative, unsigned) = strip_sign(value);
+    if matches!(unsigned, ".inf" | ".Inf" | ".INF") {
+        return Some(if negative {
+            f64::NEG_INFINITY
+        } else {
+            f64::INFINITY
+        });
+    }
+
+    let is_digits = |digits: &str| digits.bytes().all(|byte| byte.is_ascii_digit());
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(index) => (&unsigned[..index], Some(&unsigned[index + 1..])),
+        None => (unsigned, None),
+    };
+    let valid_mantissa = match mantissa.split_once('.') {
+        Some((integer, fraction)) => {
+            is_digits(integer)
+                && is_digits(fraction)
+                && !(integer.is_empty() && fraction.is_empty())
+        }
+        None => !mantissa.is_empty() && is_digits(mantissa),
+    };
+    let valid_exponent = exponent.map_or(true, |exponent| {
+        let (_, digits) = strip_sign(exponent);
+        !digits.is_empty() && is_digits(digits)
+    });
+    if valid_mantissa && valid_exponent {
+        value.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn strip_sign(value: &str) -> (bool, &str) {
+    if let Some(rest) = value.strip_prefix('-') {
+        (true, rest)
+    } else {
+        (false, value.strip_prefix('+').unwrap_or(value))
+    }
+}
+
+fn parse_digits(digits: &str, radix: u32) -> Option<i128> {
+    if digits.is_empty() || !digits.chars().all(|ch| ch.is_digit(radix)) {
+        return None;
+    }
+    i128::from_str_radix(digits, radix).ok()
+}
+
+fn all_bytes(value: &str, pred: impl Fn(u8) -> bool) -> bool {
+    !value.is_empty() && value.bytes().all(pred)
+}
+
+/// Match `-?(0|[1-9][0-9]*)`.
+fn is_json_int(value: &str) -> bool {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    digits == "0" || !digits.starts_with('0') && all_bytes(digits, |byte| byte.is_ascii_digit())
+}
+
+/// Match `-?(0|[1-9][0-9]*)(\.[0-9]*)?([eE][-+]?[0-9]+)?`.
+fn is_json_float(value: &str) -> bool {
+    let (mantissa, exponent) = match value.find(['e', 'E']) {
+        Some(index) => (&value[..index], Some(&value[index + 1..])),
+        None => (value, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, fraction),
+        None => (mantissa, ""),
+    };
+    is_json_int(integer)
+        && fraction.bytes().all(|byte| byte.is_ascii_digit())
+        && exponent.map_or(true, |exponent| {
+            let (_, digits) = strip_sign(exponent);
+            all_bytes(digits, |byte| byte.is_ascii_digit())
+        })
+}
+
+/// Match `[-+]?[0-9]+`, `0o[0-7]+` or `0x[0-9a-fA-F]+`.
+fn is_core_int(value: &str) -> bool {
+    if let Some(hex) = value.strip_prefix("0x") {
+        return all_bytes(hex, |byte| byte.is_ascii_hexdigit());
+    }
+    if let Some(octal) = value.strip_prefix("0o") {
+        return all_bytes(octal, |byte| matches!(byte, b'0'..=b'7'));
+    }
+    let (_, digits) = strip_sign(value);
+    all_bytes(digits, |byte| byte.is_ascii_digit())
+}
+
+/// Parse a YAML 1.1 `!!bool` value.
+pub(crate) fn parse_yaml11_bool(value: &str) -> Option<bool> {
+    match value {
+        "y" | "Y" | "yes" | "Yes" | "YES" | "true" | "True" | "TRUE" | "on" | "On" | "ON" => {
+            Some(true)
+        }
+        "n" | "N" | "no" | "No" | "NO" | "false" | "False" | "FALSE" | "off" | "Off" | "OFF" => {
+            Some(false)
+        }
+        _ => None,
+    }
+}
+
+/// Match the sexagesimal digits `(:[0-5]?[0-9])+` after the first colon.
+fn is_sexagesimal(rest: &str) -> bool {
+    rest.split(':').all(|digits| match digits.as_bytes() {
+        [digit] => digit.is_ascii_digit(),
+        [tens, digit] => matches!(tens, b'0'..=b'5') && digit.is_ascii_digit(),
+        _ => false,
+    })
+}
+
+/// Match a YAML 1.1 `!!int`: a binary (`0b`), octal (leading `0`), decimal,
+/// hexadecimal (`0x`) or sexagesimal integer with an optional sign and
+/// underscores.
+fn is_yaml11_int(value: &str) -> bool {
+    let (_, unsigned) = strip_sign(value);
+    if let Some(binary) = unsigned.strip_prefix("0b") {
+        return all_bytes(binary, |byte| matches!(byte, b'0' | b'1' | b'_'));
+    }
+    if let Some(hex) = unsigned.strip_prefix("0x") {
+        return all_bytes(hex, |byte| byte.is_ascii_hexdigit() || byte == b'_');
+    }
+    if unsigned == "0" {
+        return true;
+    }
+    if let Some(octal) = unsigned.strip_prefix('0') {
+        return all_bytes(octal, |byte| matches!(byte, b'0'..=b'7' | b'_'));
+    }
+    let is_decimal = |digits: &str| {
+        digits.starts_with(|ch: char| ch.is_ascii_digit())
+            && all_bytes(digits, |byte| byte.is_ascii_digit() || byte == b'_')
+    };
+    match unsigned.split_once(':') {
+        Some((first, rest)) => is_decimal(first) && is_sexagesimal(rest),
+        None => is_decimal(unsigned),
+    }
+}
+
+/// Parse a YAML 1.1 `!!int` value (see [`is_yaml11_int()`]).
+///
+/// Returns `None` if the value is not an integer, or if it does not fit in an
+/// `i128`.
+pub(crate) fn parse_yaml11_int(value: &str) -> Option<i128> {
+    if !is_yaml11_int(value) {
+        return None;
+    }
+    let (negative, unsigned) = strip_sign(value);
+    let digits = unsigned.replace('_', "");
+    let magnitude = if let Some(binary) = digits.strip_prefix("0b") {
+        parse_digits(binary, 2)?
+    } else if let Some(hex) = digits.strip_prefix("0x") {
+        parse_digits(hex, 16)?
+    } else if digits.contains(':') {
+        parse_sexagesimal(&digits, |digits| parse_digits(digits, 10))?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        parse_digits(&digits[1..], 8)?
+    } else {
+        parse_digits(&digits, 10)?
+    };
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// Parse a YAML 1.1 `!!float` value (see [`is_yaml11_float()`]).
+pub(crate) fn parse_yaml11_float(value: &str) -> Option<f64> {
+    if !is_yaml11_float(value) {
+        return None;
+    }
+    if matches!(value, ".nan" | ".NaN" | ".NAN") {
+        return Some(f64::NAN);
+    }
+    let (negative, unsigned) = strip_sign(value);
+    let magnitude = if matches!(unsigned, ".inf" | ".Inf" | ".INF") {
+        f64::INFINITY
+    } else {
+        let digits = unsigned.replace('_', "");
+        if digits.contains(':') {
+            parse_sexagesimal(&digits, |digits| digits.parse().ok())?
+        } else {
+            digits.parse().ok()?
+        }
+    };
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+/// Parse the base 60 number `digits`, whose colon-separated parts are parsed
+/// with `parse`.
+fn parse_sexagesimal<T>(digits: &str, parse: impl Fn(&str) -> Option<T>) -> Option<T>
+where
+    T: core::ops::Mul<Output = T> + core::ops::Add<Output = T> + From<u8>,
+{
+    let mut parts = digits.split(':');
+    let mut value = parse(parts.next()?)?;
+    for part in parts {
+        value = value * T::from(60) + parse(part)?;
+    }
+    Some(value)
+}
+
+/// Match a YAML 1.1 `!!float`: a number with a decimal point, an optional
+/// sign, underscores and an exponent, a sexagesimal number with a decimal
+/// point, `.inf` or `.nan`.
+fn is_yaml11_float(value: &str) -> bool {
+    if matches!(value, ".nan" | ".NaN" | ".NAN") {
+        return true;
+    }
+    let (_, unsigned) = strip_sign(value);
+    if matches!(unsigned, ".inf" | ".Inf" | ".INF") {
+        return true;
+    }
+    let is_digits = |digits: &str| {
+        digits
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || byte == b'_')
+    };
+    let is_integer =
+        |digits: &str| digits.starts_with(|ch: char| ch.is_ascii_digit()) && is_digits(digits);
+
+    if let Some((first, rest)) = unsigned.split_once(':') {
+        return rest.split_once('.').is_some_and(|(base60, fraction)| {
+            is_integer(first) && is_sexagesimal(base60) && is_digits(fraction)
+        });
+    }
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(index) => (&unsigned[..index], Some(&unsigned[index + 1..])),
+        None => (unsigned, None),
+    };
+    let valid_mantissa = mantissa.split_once('.').is_some_and(|(integer, fraction)| {
+        if integer.is_empty() {
+            !fraction.is_empty() && is_digits(fraction)
+        } else {
+            is_integer(integer) && is_digits(fraction)
+        }
+    });
+    let valid_exponent = exponent.map_or(true, |exponent| {
+        exponent
+            .strip_prefix(['-', '+'])
+            .is_some_and(|digits| all_bytes(digits, |byte| byte.is_ascii_digit()))
+    });
+    valid_mantissa && valid_exponent
+}
+
+/// Match a YAML 1.1 `!!timestamp`: `YYYY-MM-DD`, or a date and a time with
+/// optional fractional seconds and time zone, such as
+/// `2001-12-14t21:59:43.10-05:00`.
+fn is_yaml11_timestamp(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    let mut pos = 0;
+    // Consume between `min` and `max` ASCII digits.
+    let digits = |pos: &mut usize, min: usize, max: usize| {
+        let count = bytes[*pos..]
+            .iter()
+            .take(max)
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        *pos += count;
+        count >= min
+    };
+    let byte = |pos: &mut usize, expected: u8| {
+        let found = bytes.get(*pos) == Some(&expected);
+        if found {
+            *pos += 1;
+        }
+        found
+    };
+    let spaces = |pos: &mut usize| {
+        let count = bytes[*pos..]
+            .iter()
+            .take_while(|byte| matches!(byte, b' ' | b'\t'))
+            .count();
+        *pos += count;
+        count
+    };
+
+    if !(digits(&mut pos, 4, 4) && byte(&mut pos, b'-')) {
+        return false;
+    }
+    let month = pos;
+    if !(digits(&mut pos, 1, 2) && byte(&mut pos, b'-') && digits(&mut pos, 1, 2)) {
+        return false;
+    }
+    if pos == bytes.len() {
+        // A date without a time has two-digit months and days.
+        return pos - month == 5;
+    }
+    if !(byte(&mut pos, b'T') || byte(&mut pos, b't') || spaces(&mut pos) > 0) {
+        return false;
+    }
+    if !(digits(&mut pos, 1, 2)
+        && byte(&mut pos, b':')
+        && digits(&mut pos, 2, 2)
+        && byte(&mut pos, b':')
+        && digits(&mut pos, 2, 2))
+    {
+        return false;
+    }
+    if byte(&mut pos, b'.') {
+        digits(&mut pos, 0, usize::MAX);
+    }
+    let trailing_spaces = spaces(&mut pos) > 0;
+    if pos == bytes.len() {
+        return !trailing_spaces;
+    }
+    if byte(&mut pos, b'Z') {
+        return pos == bytes.len();
+    }
+    if !((byte(&mut pos, b'-') || byte(&mut pos, b'+')) && digits(&mut pos, 1, 2)) {
+        return false;
+    }
+    if byte(&mut pos, b':') && !digits(&mut pos, 2, 2) {
+        return false;
+    }
+    pos == bytes.len()
+}
@@ -0,0 +1,473 @@
+use serde::ser::{self, Serialize};
+
+use crate::schema::{is_null, parse_bool, parse_float, parse_int};
+use crate::{Emitter, Error, Event, MappingStyle, Result, ScalarStyle, SequenceStyle};
+
+/// Serialize `value` as a YAML document into a string.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    let mut output = Vec::new();
+    let mut emitter = Emitter::new();
+    emitter.set_output_string(&mut output);
+    emitter.set_unicode(true);
+    let mut serializer = Serializer::new(emitter);
+    value.serialize(&mut serializer)?;
+    serializer.end()?;
+    drop(serializer);
+    Ok(String::from_utf8(output).expect("the emitter writes UTF-8"))
+}
+
+/// Serialize `value` as a YAML document into a writer.
+///
+/// See [`to_string()`].
+pub fn to_writer<W: std::io::Write, T: Serialize + ?Sized>(mut writer: W, value: &T) -> Result<()> {
+    let mut emitter = Emitter::new();
+    emitter.set_output(&mut writer);
+    emitter.set_unicode(true);
+    let mut serializer = Serializer::new(emitter);
+    value.serialize(&mut serializer)?;
+    serializer.end()
+}
+
+/// A serde serializer that writes YAML documents to an [`Emitter`].
+///
+/// Each serialized value is a document of its own. Strings that would
+/// otherwise be read back as another type of the YAML 1.2 core schema, such as
+/// `"true"`, `"1.0"` or `"null"`, are quoted. Numbers, booleans and null
+/// values are always written as plain scalars.
+///
+/// Enum variants without data are written as strings, and other variants as a
+/// mapping with a single key, the name of the variant.
+pub struct Serializer<'w> {
+    emitter: Emitter<'w>,
+    /// Has the STREAM-START event been emitted?
+    started: bool,
+    /// The nesting depth of the current node.
+    depth: usize,
+    scalar_style: ScalarStyle,
+    sequence_style: SequenceStyle,
+    mapping_style: MappingStyle,
+}
+
+impl<'w> Serializer<'w> {
+    /// Create a serializer that writes to `emitter`.
+    ///
+    /// The emitter must have an output, and must not be opened yet.
+    pub fn new(emitter: Emitter<'w>) -> Self {
+        Self {
+            emitter,
+            started: false,
+            depth: 0,
+            scalar_style: ScalarStyle::Any,
+            sequence_style: SequenceStyle::Any,
+            mapping_style: MappingStyle::Any,
+        }
+    }
+
+    /// Set the preferred style of string scalars.
+    ///
+    /// The emitter falls back to a quoted style where the preferred style
+    /// cannot represent the string, or would change its type.
+    pub fn set_scalar_style(&mut self, style: ScalarStyle) {
+        self.scalar_style = style;
+    }
+
+    /// Set the preferred style of sequences.
+    pub fn set_sequence_style(&mut self, style: SequenceStyle) {
+        self.sequence_style = style;
+    }
+
+    /// Set the preferred style of mappings.
+    pub fn set_mapping_style(&mut self, style: MappingStyle) {
+        self.mapping_style = style;
+    }
+
+    /// Finish the YAML stream and flush the emitter.
+    ///
+    /// Call this after serializing the values.
+    pub fn end(&mut self) -> Result<()> {
+        if !self.started {
+            self.emitter.open()?;
+            self.started = true;
+        }
+        self.emitter.close()
+    }
+
+    fn start_node(&mut self) -> Result<()> {
+        if self.depth == 0 {
+            if !self.started {
+                self.emitter.open()?;
+                self.started = true;
+            }
+            self.emitter.emit(Event::document_start(None, &[], true))?;
+        }
+        Ok(())
+    }
+
+    fn end_node(&mut self) -> Result<()> {
+        if self.depth == 0 {
+            self.emitter.emit(Event::document_end(true))?;
+        }
+        Ok(())
+    }
+
+    /// Emit a plain scalar that resolves to a type other than `!!str`.
+    fn emit_plain(&mut self, value: &str) -> Result<()> {
+        self.start_node()?;
+        self.emitter.emit(Event::scalar(
+            None,
+            None,
+            value,
+            true,
+            false,
+            ScalarStyle::Plain,
+        ))?;
+        self.end_node()
+    }
+
+    fn emit_str(&mut self, value: &str) -> Result<()> {
+        // A string that would resolve to another type must not be plain.
+        let plain_implicit = !is_null(value)
+            && parse_bool(value).is_none()
+            && parse_int(value).is_none()
+            && parse_float(value).is_none();
+        self.start_node()?;
+        self.emitter.emit(Event::scalar(
+            None,
+            None,
+            value,
+            plain_implicit,
+            true,
+            self.scalar_style,
+        ))?;
+        self.end_node()
+    }
+
+    fn start_sequence(&mut self) -> Result<()> {
+        self.start_node()?;
+        self.emitter
+            .emit(Event::sequence_start(None, None, true, self.sequence_style))?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn end_sequence(&mut self) -> Result<()> {
+        self.emitter.emit(Event::sequence_end())?;
+        self.depth -= 1;
+        self.end_node()
+    }
+
+    fn start_mapping(&mut self) -> Result<()> {
+        self.start_node()?;
+        self.emitter
+            .emit(Event::mapping_start(None, None, true, self.mapping_style))?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn end_mapping(&mut self) -> Result<()> {
+        self.emitter.emit(Event::mapping_end())?;
+        self.depth -= 1;
+        self.end_node()
+    }
+
+    /// Start a mapping with the single key `variant`, for an enum variant with
+    /// data.
+    fn start_variant(&mut self, variant: &str) -> Result<()> {
+        self.start_mapping()?;
+        self.emit_str(variant)
+    }
+
+    fn emit_float(&mut self, value: f64) -> Result<()> {
+        if value.is_nan() {
+            self.emit_plain(".nan")
+        } else if value.is_infinite() {
+            self.emit_plain(if value > 0.0 { ".inf" } else { "-.inf" })
+        } else {
+            // The `Debug` format always includes a fraction or an exponent,
+            // so the value is not read back as an integer.
+            self.emit_plain(&format!("{value:?}"))
+        }
+    }
+}
+
+impl ser::Serializer for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.emit_plain(if v { "true" } else { "false" })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        self.emit_plain(&v.to_string())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        if v.is_finite() {
+            // Format as `f32`, so that `0.1` is not written as `0.10000000149011612`.
+            self.emit_plain(&format!("{v:?}"))
+        } else {
+            self.emit_float(f64::from(v))
+        }
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.emit_float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.emit_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.emit_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        ser::Serializer::collect_seq(self, v)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.emit_plain("null")
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.emit_plain("null")
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.emit_plain("null")
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.emit_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.start_variant(variant)?;
+        value.serialize(&mut *self)?;
+        self.end_mapping()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self> {
+        self.start_sequence()?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        self.start_sequence()?;
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        self.start_sequence()?;
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.start_variant(variant)?;
+        self.start_sequence()?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self> {
+        self.start_mapping()?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        self.start_mapping()?;
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.start_variant(variant)?;
+        self.start_mapping()?;
+        Ok(self)
+    }
+}
+
+impl ser::SerializeSeq for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_sequence()
+    }
+}
+
+impl ser::SerializeTuple for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_sequence()
+    }
+}
+
+impl ser::SerializeTupleStruct for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_sequence()
+    }
+}
+
+impl ser::SerializeTupleVariant for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_sequence()?;
+        self.end_mapping()
+    }
+}
+
+impl ser::SerializeMap for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_mapping()
+    }
+}
+
+impl ser::SerializeStruct for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.emit_str(key)?;
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_mapping()
+    }
+}
+
+impl ser::SerializeStructVariant for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.emit_str(key)?;
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_mapping()?;
+        self.end_mapping()
+    }
+}
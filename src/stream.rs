@@ -0,0 +1,82 @@
+use crate::{Document, Emitter, Parser, Result};
+
+/// All the documents of a YAML stream.
+#[derive(Clone, Default, Debug)]
+#[non_exhaustive]
+pub struct Stream {
+    /// The documents, in stream order.
+    pub documents: Vec<Document>,
+}
+
+impl Stream {
+    /// Create an empty stream.
+    pub fn new() -> Stream {
+        Stream::default()
+    }
+
+    /// Parse the input stream and produce all of its documents.
+    ///
+    /// See [`Parser::documents()`].
+    pub fn load(parser: &mut Parser) -> Result<Stream> {
+        let documents = parser.documents().collect::<Result<_>>()?;
+        Ok(Stream { documents })
+    }
+
+    /// Emit all documents of the stream, then close the emitter.
+    ///
+    /// Unlike [`Document::dump()`], an empty document does not close the
+    /// emitter; it is skipped.
+    pub fn dump(&self, emitter: &mut Emitter) -> Result<()> {
+        if !emitter.opened {
+            emitter.open()?;
+        }
+        for document in &self.documents {
+            for event in document.events_for(emitter.schema) {
+                emitter.emit(event)?;
+            }
+        }
+        emitter.close()
+    }
+}
+
+/// An iterator over the documents of a YAML stream.
+///
+/// See [`Parser::documents()`].
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct Documents<'a, 'r> {
+    parser: &'a mut Parser<'r>,
+    done: bool,
+}
+
+impl<'a, 'r> Documents<'a, 'r> {
+    pub(crate) fn new(parser: &'a mut Parser<'r>) -> Self {
+        Documents {
+            parser,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Documents<'_, '_> {
+    type Item = Result<Document>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match Document::load(self.parser) {
+            // Only the end of the stream produces a document without nodes.
+            Ok(document) if document.nodes.is_empty() => {
+                self.done = true;
+                None
+            }
+            Ok(document) => Some(Ok(document)),
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl core::iter::FusedIterator for Documents<'_, '_> {}
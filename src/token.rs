@@ -1,19 +1,29 @@
+use alloc::borrow::Cow;
+
 use crate::{Encoding, Mark, ScalarStyle};
 
 /// The token structure.
+///
+/// The string values of the token are of type `S`, which is [`String`] unless
+/// the token is a [`BorrowedToken`].
 #[derive(Debug, PartialEq)]
 #[non_exhaustive]
-pub struct Token {
+pub struct Token<S = String> {
     /// The token type.
-    pub data: TokenData,
+    pub data: TokenData<S>,
     /// The beginning of the token.
     pub start_mark: Mark,
     /// The end of the token.
     pub end_mark: Mark,
 }
 
+/// A token that borrows its values from an in-memory input where possible.
+///
+/// See [`Scanner::scan_borrowed()`](crate::Scanner::scan_borrowed).
+pub type BorrowedToken<'input> = Token<Cow<'input, str>>;
+
 #[derive(Debug, PartialEq)]
-pub enum TokenData {
+pub enum TokenData<S = String> {
     /// A STREAM-START token.
     StreamStart {
         /// The stream encoding.
@@ -31,9 +41,9 @@ pub enum TokenData {
     /// A TAG-DIRECTIVE token.
     TagDirective {
         /// The tag handle.
-        handle: String,
+        handle: S,
         /// The tag prefix.
-        prefix: String,
+        prefix: S,
     },
     /// A DOCUMENT-START token.
     DocumentStart,
@@ -64,25 +74,95 @@ pub enum TokenData {
     /// An ALIAS token.
     Alias {
         /// The alias value.
-        value: String,
+        value: S,
     },
     /// An ANCHOR token.
     Anchor {
         /// The anchor value.
-        value: String,
+        value: S,
     },
     /// A TAG token.
     Tag {
         /// The tag handle.
-        handle: String,
+        handle: S,
         /// The tag suffix.
-        suffix: String,
+        suffix: S,
     },
     /// A SCALAR token.
     Scalar {
         /// The scalar value.
-        value: String,
+        value: S,
         /// The scalar style.
         style: ScalarStyle,
     },
+    /// A COMMENT token.
+    ///
+    /// Only produced when comments are preserved, see
+    /// [`Scanner::set_preserve_comments()`](crate::Scanner::set_preserve_comments).
+    Comment {
+        /// The comment text, following the `#` indicator up to (but not
+        /// including) the line break.
+        value: S,
+        /// Is the comment on the same line as a preceding token?
+        inline: bool,
+    },
+}
+
+impl BorrowedToken<'_> {
+    /// Copy the borrowed values of the token.
+    pub fn into_owned(self) -> Token {
+        Token {
+            data: self.data.into_owned(),
+            start_mark: self.start_mark,
+            end_mark: self.end_mark,
+        }
+    }
+}
+
+impl TokenData<Cow<'_, str>> {
+    /// Copy the borrowed values of the token data.
+    pub fn into_owned(self) -> TokenData {
+        match self {
+            TokenData::StreamStart { encoding } => TokenData::StreamStart { encoding },
+            TokenData::StreamEnd => TokenData::StreamEnd,
+            TokenData::VersionDirective { major, minor } => {
+                TokenData::VersionDirective { major, minor }
+            }
+            TokenData::TagDirective { handle, prefix } => TokenData::TagDirective {
+                handle: handle.into_owned(),
+                prefix: prefix.into_owned(),
+            },
+            TokenData::DocumentStart => TokenData::DocumentStart,
+            TokenData::DocumentEnd => TokenData::DocumentEnd,
+            TokenData::BlockSequenceStart => TokenData::BlockSequenceStart,
+            TokenData::BlockMappingStart => TokenData::BlockMappingStart,
+            TokenData::BlockEnd => TokenData::BlockEnd,
+            TokenData::FlowSequenceStart => TokenData::FlowSequenceStart,
+            TokenData::FlowSequenceEnd => TokenData::FlowSequenceEnd,
+            TokenData::FlowMappingStart => TokenData::FlowMappingStart,
+            TokenData::FlowMappingEnd => TokenData::FlowMappingEnd,
+            TokenData::BlockEntry => TokenData::BlockEntry,
+            TokenData::FlowEntry => TokenData::FlowEntry,
+            TokenData::Key => TokenData::Key,
+            TokenData::Value => TokenData::Value,
+            TokenData::Alias { value } => TokenData::Alias {
+                value: value.into_owned(),
+            },
+            TokenData::Anchor { value } => TokenData::Anchor {
+                value: value.into_owned(),
+            },
+            TokenData::Tag { handle, suffix } => TokenData::Tag {
+                handle: handle.into_owned(),
+                suffix: suffix.into_owned(),
+            },
+            TokenData::Scalar { value, style } => TokenData::Scalar {
+                value: value.into_owned(),
+                style,
+            },
+            TokenData::Comment { value, inline } => TokenData::Comment {
+                value: value.into_owned(),
+                inline,
+            },
+        }
+    }
 }
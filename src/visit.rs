@@ -0,0 +1,225 @@
+use std::collections::HashSet;
+
+use crate::{Document, Node, NodeData, NodeId, NodePair};
+
+/// A visitor of the nodes of a [`Document`].
+///
+/// The walk starts at the root node, and visits the items of a sequence and
+/// the values of a mapping between its `enter_` and `exit_` callbacks. The
+/// keys of a mapping are passed to [`Visitor::visit_pair()`], and are not
+/// walked. Every callback receives the path of the node from the root node,
+/// as in [`Document::get_path()`].
+///
+/// See [`Document::walk()`].
+#[allow(unused_variables)]
+pub trait Visitor {
+    /// Visit a scalar node.
+    fn visit_scalar(&mut self, path: &[String], id: NodeId, node: &Node) {}
+
+    /// Visit a sequence node, before its items.
+    fn enter_sequence(&mut self, path: &[String], id: NodeId, node: &Node) {}
+
+    /// Visit a sequence node, after its items.
+    fn exit_sequence(&mut self, path: &[String], id: NodeId, node: &Node) {}
+
+    /// Visit a mapping node, before its pairs.
+    fn enter_mapping(&mut self, path: &[String], id: NodeId, node: &Node) {}
+
+    /// Visit a pair of a mapping node, before its value. The path is the path
+    /// of the value.
+    fn visit_pair(&mut self, path: &[String], pair: NodePair) {}
+
+    /// Visit a mapping node, after its pairs.
+    fn exit_mapping(&mut self, path: &[String], id: NodeId, node: &Node) {}
+
+    /// Visit an alias to a collection that contains it. The collection `id`
+    /// is not walked again.
+    fn visit_cycle(&mut self, path: &[String], id: NodeId) {}
+
+    /// Visit an alias to the node `id`, which was already walked. Returns if
+    /// the node is walked again, which it is by default.
+    ///
+    /// Return `false` to walk each node once: walking every alias again takes
+    /// time exponential in the size of the document for aliases of aliases.
+    fn visit_repeat(&mut self, path: &[String], id: NodeId) -> bool {
+        true
+    }
+}
+
+/// A visitor that may modify the nodes of a [`Document`].
+///
+/// Like [`Visitor`], but the nodes are mutable. Changes to the items or
+/// pairs of a collection in its `enter_` callback are taken into account by
+/// the walk.
+///
+/// See [`Document::walk_mut()`].
+#[allow(unused_variables)]
+pub trait VisitorMut {
+    /// Visit a scalar node.
+    fn visit_scalar(&mut self, path: &[String], id: NodeId, node: &mut Node) {}
+
+    /// Visit a sequence node, before its items.
+    fn enter_sequence(&mut self, path: &[String], id: NodeId, node: &mut Node) {}
+
+    /// Visit a sequence node, after its items.
+    fn exit_sequence(&mut self, path: &[String], id: NodeId, node: &mut Node) {}
+
+    /// Visit a mapping node, before its pairs.
+    fn enter_mapping(&mut self, path: &[String], id: NodeId, node: &mut Node) {}
+
+    /// Visit a pair of a mapping node, before its value. The path is the path
+    /// of the value.
+    fn visit_pair(&mut self, path: &[String], pair: NodePair) {}
+
+    /// Visit a mapping node, after its pairs.
+    fn exit_mapping(&mut self, path: &[String], id: NodeId, node: &mut Node) {}
+
+    /// Visit an alias to a collection that contains it. The collection `id`
+    /// is not walked again.
+    fn visit_cycle(&mut self, path: &[String], id: NodeId) {}
+
+    /// Visit an alias to the node `id`, which was already walked. Returns if
+    /// the node is walked again, which it is by default.
+    ///
+    /// Return `false` to walk each node once: walking every alias again takes
+    /// time exponential in the size of the document for aliases of aliases.
+    fn visit_repeat(&mut self, path: &[String], id: NodeId) -> bool {
+        true
+    }
+}
+
+impl Document {
+    /// Walk the nodes of the document with `visitor`, from the root node.
+    ///
+    /// A node that is referred to by several aliases is visited once for each
+    /// of them, unless the alias is inside the node itself. As aliases may
+    /// refer to nodes with aliases, this can take time exponential in the size
+    /// of the document. A visitor whose [`Visitor::visit_repeat()`] returns
+    /// `false` visits each node once instead.
+    pub fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        if !self.nodes.is_empty() {
+            let root = NodeId::from_index(0);
+            self.walk_node(
+                visitor,
+                root,
+                &mut Vec::new(),
+                &mut Vec::new(),
+                &mut HashSet::new(),
+            );
+        }
+    }
+
+    /// Walk the nodes of the document with `visitor`, which may modify them.
+    ///
+    /// See [`Document::walk()`].
+    pub fn walk_mut<V: VisitorMut + ?Sized>(&mut self, visitor: &mut V) {
+        if !self.nodes.is_empty() {
+            let root = NodeId::from_index(0);
+            self.walk_node_mut(
+                visitor,
+                root,
+                &mut Vec::new(),
+                &mut Vec::new(),
+                &mut HashSet::new(),
+            );
+        }
+    }
+
+    /// `ancestors` holds the collections being walked, to detect cycles, and
+    /// `walked` the nodes walked so far.
+    fn walk_node<V: Visitor + ?Sized>(
+        &self,
+        visitor: &mut V,
+        id: NodeId,
+        path: &mut Vec<String>,
+        ancestors: &mut Vec<NodeId>,
+        walked: &mut HashSet<NodeId>,
+    ) {
+        if ancestors.contains(&id) {
+            visitor.visit_cycle(path, id);
+            return;
+        }
+        if !walked.insert(id) && !visitor.visit_repeat(path, id) {
+            return;
+        }
+        let node = &self.nodes[id.index()];
+        match &node.data {
+            NodeData::Scalar { .. } => visitor.visit_scalar(path, id, node),
+            NodeData::Sequence { items, .. } => {
+                visitor.enter_sequence(path, id, node);
+                ancestors.push(id);
+                for (index, item) in items.iter().enumerate() {
+                    path.push(index.to_string());
+                    self.walk_node(visitor, *item, path, ancestors, walked);
+                    path.pop();
+                }
+                ancestors.pop();
+                visitor.exit_sequence(path, id, node);
+            }
+            NodeData::Mapping { pairs, .. } => {
+                visitor.enter_mapping(path, id, node);
+                ancestors.push(id);
+                for pair in pairs {
+                    path.push(self.key_text(pair.key));
+                    visitor.visit_pair(path, *pair);
+                    self.walk_node(visitor, pair.value, path, ancestors, walked);
+                    path.pop();
+                }
+                ancestors.pop();
+                visitor.exit_mapping(path, id, node);
+            }
+            NodeData::NoNode => {}
+        }
+    }
+
+    fn walk_node_mut<V: VisitorMut + ?Sized>(
+        &mut self,
+        visitor: &mut V,
+        id: NodeId,
+        path: &mut Vec<String>,
+        ancestors: &mut Vec<NodeId>,
+        walked: &mut HashSet<NodeId>,
+    ) {
+        if ancestors.contains(&id) {
+            visitor.visit_cycle(path, id);
+            return;
+        }
+        if !walked.insert(id) && !visitor.visit_repeat(path, id) {
+            return;
+        }
+        match self.nodes[id.index()].data {
+            NodeData::Scalar { .. } => visitor.visit_scalar(path, id, &mut self.nodes[id.index()]),
+            NodeData::Sequence { .. } => {
+                visitor.enter_sequence(path, id, &mut self.nodes[id.index()]);
+                // The visitor may have changed the node.
+                if let NodeData::Sequence { items, .. } = &self.nodes[id.index()].data {
+                    let items = items.clone();
+                    ancestors.push(id);
+                    for (index, item) in items.into_iter().enumerate() {
+                        path.push(index.to_string());
+                        self.walk_node_mut(visitor, item, path, ancestors, walked);
+                        path.pop();
+                    }
+                    ancestors.pop();
+                }
+                visitor.exit_sequence(path, id, &mut self.nodes[id.index()]);
+            }
+            NodeData::Mapping { .. } => {
+                visitor.enter_mapping(path, id, &mut self.nodes[id.index()]);
+                if let NodeData::Mapping { pairs, .. } = &self.nodes[id.index()].data {
+                    let pairs = pairs.clone();
+                    ancestors.push(id);
+                    for pair in pairs {
+                        path.push(self.key_text(pair.key));
+                        visitor.visit_pair(path, pair);
+                        self.walk_node_mut(visitor, pair.value, path, ancestors, walked);
+                        path.pop();
+                    }
+                    ancestors.pop();
+                }
+                visitor.exit_mapping(path, id, &mut self.nodes[id.index()]);
+            }
+            NodeData::NoNode => {}
+        }
+    }
+}